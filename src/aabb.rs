@@ -1,120 +1,30 @@
-use crate::triangle::Triangle2D;
-use ultraviolet::Vec2;
+use ultraviolet::Vec3;
 
-#[derive(Debug)]
-pub struct AABB {
-    pub min_x: f32,
-    pub max_x: f32,
-    pub min_y: f32,
-    pub max_y: f32,
+/// An axis-aligned bounding box in 3D space.
+#[derive(Debug, Clone, Copy)]
+pub struct AABB3 {
+    pub min: Vec3,
+    pub max: Vec3,
 }
 
-impl AABB {
-    pub fn new(min_x: f32, max_x: f32, min_y: f32, max_y: f32) -> Self {
-        Self {
-            min_x,
-            max_x,
-            min_y,
-            max_y,
-        }
-    }
-
-    pub fn from_points(min: Vec2, max: Vec2) -> Self {
-        Self {
-            min_x: min.x,
-            max_x: max.x,
-            min_y: min.y,
-            max_y: max.y,
-        }
-    }
-
-    pub fn point_in_aabb(&self, point: &Vec2) -> bool {
-        point.x > self.min_x && point.x < self.max_x && point.y > self.min_y && point.y < self.max_y
-    }
-
-    fn intersects(&self, other: &Self) -> bool {
-        self.max_x > other.min_x && other.max_x > self.min_x &&
-        self.max_y > other.min_y && other.max_y > self.min_y
-    }
-
-    /// Returns the intersection of `self` and `other` as a new `AABB`.
-    /// Result is `None` if `self` and `other` do not intersect.
-    pub fn intersection(&self, other: &Self) -> Option<Self> {
-        if self.intersects(other) {
-            Some(
-                Self {
-                    min_x: f32::max(self.min_x, other.min_x),
-                    max_x: f32::min(self.max_x, other.max_x),
-                    min_y: f32::max(self.min_y, other.min_y),
-                    max_y: f32::min(self.max_y, other.max_y),
-                }
+impl AABB3 {
+    /// Returns the smallest box containing all of `points`, or `None` if there are none.
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), p| {
+            (min.min_by_component(p), max.max_by_component(p))
+        });
+        Some(Self { min, max })
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
             )
-        } else {
-            None
-        }
-    }
-}
-
-impl From<&Triangle2D> for AABB {
-    fn from(value: &Triangle2D) -> Self {
-        AABB {
-            min_x: value.v0.x.min(value.v1.x).min(value.v2.x),
-            max_x: value.v0.x.max(value.v1.x).max(value.v2.x),
-            min_y: value.v0.y.min(value.v1.y).min(value.v2.y),
-            max_y: value.v0.y.max(value.v1.y).max(value.v2.y),
-        }
-    }
-}
-
-impl IntoIterator for AABB {
-    type Item = Vec2;
-
-    type IntoIter = AABBIter;
-
-    fn into_iter(self) -> Self::IntoIter {
-        return AABBIter::new(self);
-    }
-}
-
-pub struct AABBIter {
-    inner: AABB,
-    x: f32,
-    y: f32,
-    ran_size_check: bool,
-}
-
-impl AABBIter {
-    fn new(inner: AABB) -> Self {
-        let x = inner.min_x.floor();
-        let y = inner.min_y.floor();
-        Self { inner, x, y, ran_size_check: false }
-    }
-}
-
-impl Iterator for AABBIter {
-    type Item = Vec2;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if !self.ran_size_check {
-            if self.inner.max_x - self.inner.min_x < 1. || self.inner.max_y - self.inner.min_y < 1. {
-                return None;
-            } else {
-                self.ran_size_check = true;
-            }
-        }
-
-        if self.x < self.inner.max_x {
-            if self.y < self.inner.max_y {
-                let x = self.x;
-                self.x += 1.;
-                return Some(Vec2::new(x, self.y));
-            } else {
-                return None;
-            }
-        } else {
-            self.x = self.inner.min_x.floor();
-            self.y += 1.;
-            return self.next();
-        }
+        })
     }
 }
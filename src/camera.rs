@@ -1,21 +1,33 @@
 use crate::{
-    aabb::AABB,
-    triangle::{Triangle2D, Triangle3D},
+    aabb::AABB3,
+    clipping::{clip_triangle, ClipVertex},
+    object::{normal_matrix, Object},
+    rasterizer::ScreenVertex,
+    shader::{Lighting, Uniforms, Varying, VertexInput, VertexShader},
+    triangle::Winding,
 };
-use ultraviolet::{projection, Isometry3, Mat4, Rotor3, Vec2, Vec3};
+use ultraviolet::{Isometry3, Mat3, Mat4, Rotor3, Vec3, Vec4};
 
 pub struct Camera {
     pub position: Vec3,
     pub rotation: Rotor3,
 
     pub vertical_fov: f32,
+    /// If set, the camera uses an orthographic projection covering this many world units
+    /// vertically, instead of a perspective projection using `vertical_fov`.
+    pub orthographic_height: Option<f32>,
     pub z_near: f32,
     pub z_far: f32,
+    /// Whether depth goes from 1 at `z_near` to 0 at `z_far`, instead of from 0 to 1. Floats
+    /// are most precise near 0, which balances out the perspective divide bunching distant
+    /// depths together. Depth buffers have to match, see
+    /// [`DepthBuffer::for_camera`](crate::depth_buffer::DepthBuffer::for_camera).
+    // ? https://developer.nvidia.com/content/depth-precision-visualized
+    pub reversed_z: bool,
     pub projection_matrix: Mat4,
 
     pub screen_width: f32,
     pub screen_height: f32,
-    pub screen_aabb: AABB,
 }
 
 impl Camera {
@@ -32,12 +44,13 @@ impl Camera {
             position,
             rotation,
             vertical_fov,
+            orthographic_height: None,
             z_near,
             z_far,
+            reversed_z: true,
             projection_matrix: Mat4::identity(),
             screen_width: 0.,
             screen_height: 0.,
-            screen_aabb: AABB::new(0., 0., 0., 0.),
         };
         res.update_screen_dimensions(width, height);
         res
@@ -47,6 +60,18 @@ impl Camera {
         Isometry3::new(self.position, self.rotation).inversed()
     }
 
+    /// Moves the camera by `offset` along its own axes, where x points left, y points down and
+    /// z points forward.
+    pub fn move_local(&mut self, offset: Vec3) {
+        self.position += offset.rotated_by(self.rotation);
+    }
+
+    /// Turns the camera by `angle` radians around the world's y axis.
+    pub fn turn(&mut self, angle: f32) {
+        self.rotation = Rotor3::from_rotation_xz(angle) * self.rotation;
+        self.rotation.normalize();
+    }
+
     fn aspect_ratio(&self) -> f32 {
         self.screen_width / self.screen_height
     }
@@ -55,42 +80,328 @@ impl Camera {
         self.screen_width = width as f32;
         self.screen_height = height as f32;
 
-        self.screen_aabb = AABB::new(0., self.screen_width, 0., self.screen_height);
-        // ? https://developer.nvidia.com/content/depth-precision-visualized
-        self.projection_matrix = projection::perspective_reversed_z_vk(
-            self.vertical_fov,
-            self.aspect_ratio(),
-            self.z_near,
-            self.z_far,
-        );
-    }
-
-    /// Projects a [`Triangle3D`] from the camera's local space into clip space, returning
-    /// the projected [`Triangle2D`] as well as the z-depth of the triangle's 3 vertices.
-    pub fn project_triangle(&mut self, triangle: Triangle3D) -> (Triangle2D, [f32; 3]) {
-        let projected = triangle.apply_matrix(self.projection_matrix);
-        let (cam_space, z_res) = projected.truncated_include_z();
-        (self.projected_to_screen(cam_space), z_res)
-    }
-
-    /// Transforms a triangle from clip space to pixel coordinates.
-    pub fn projected_to_screen(&self, triangle: Triangle2D) -> Triangle2D {
-        let half_width = self.screen_width / 2.;
-        let half_height = self.screen_height / 2.;
-
-        Triangle2D::new(
-            Vec2::new(
-                half_width * (triangle.v0.x + 1.),
-                half_height * (triangle.v0.y + 1.),
-            ),
-            Vec2::new(
-                half_width * (triangle.v1.x + 1.),
-                half_height * (triangle.v1.y + 1.),
-            ),
-            Vec2::new(
-                half_width * (triangle.v2.x + 1.),
-                half_height * (triangle.v2.y + 1.),
-            ),
+        self.projection_matrix = match self.orthographic_height {
+            Some(height) => {
+                Self::orthographic(height, self.aspect_ratio(), self.z_near, self.z_far, self.reversed_z)
+            }
+            None => {
+                Self::perspective(self.vertical_fov, self.aspect_ratio(), self.z_near, self.z_far, self.reversed_z)
+            }
+        };
+    }
+
+    /// Perspective projection for a camera looking down its local +z axis. The resulting
+    /// clip space is bounded by `-w <= x, y <= w` and `0 <= z <= w`, with `z_near` mapped
+    /// to a depth of 0 and `z_far` mapped to a depth of 1, or the other way around if `reversed_z`.
+    fn perspective(vertical_fov: f32, aspect_ratio: f32, z_near: f32, z_far: f32, reversed_z: bool) -> Mat4 {
+        let sy = 1. / (vertical_fov / 2.).tan();
+        let sx = sy / aspect_ratio;
+        let fmn = z_far - z_near;
+        let (scale_z, offset_z) = if reversed_z {
+            (-z_near / fmn, z_near * z_far / fmn)
+        } else {
+            (z_far / fmn, -z_near * z_far / fmn)
+        };
+
+        Mat4::new(
+            Vec4::new(-sx, 0., 0., 0.),
+            Vec4::new(0., sy, 0., 0.),
+            Vec4::new(0., 0., scale_z, 1.),
+            Vec4::new(0., 0., offset_z, 0.),
+        )
+    }
+
+    /// Orthographic counterpart of [`Camera::perspective`], using the same clip space conventions.
+    fn orthographic(height: f32, aspect_ratio: f32, z_near: f32, z_far: f32, reversed_z: bool) -> Mat4 {
+        let sy = 2. / height;
+        let sx = sy / aspect_ratio;
+        let fmn = z_far - z_near;
+        let (scale_z, offset_z) = if reversed_z {
+            (-1. / fmn, z_far / fmn)
+        } else {
+            (1. / fmn, -z_near / fmn)
+        };
+
+        Mat4::new(
+            Vec4::new(-sx, 0., 0., 0.),
+            Vec4::new(0., sy, 0., 0.),
+            Vec4::new(0., 0., scale_z, 0.),
+            Vec4::new(0., 0., offset_z, 1.),
+        )
+    }
+
+    /// Returns whether any part of `bounds` might be visible after being transformed by `transform`
+    /// into world space. Boxes are only rejected if all of their corners lie outside of the same
+    /// plane of the view volume, so this can give false positives near the volume's corners.
+    pub fn is_box_visible(&self, bounds: &AABB3, transform: Mat4) -> bool {
+        let clip_transform = self.projection_matrix
+            * self.get_local_space_transform().into_homogeneous_matrix()
+            * transform;
+        let corners = bounds
+            .corners()
+            .map(|corner| clip_transform * corner.into_homogeneous_point());
+
+        let outside = |inside: fn(&Vec4) -> bool| corners.iter().all(|c| !inside(c));
+        !(outside(|c| c.z >= 0.)
+            || outside(|c| c.z <= c.w)
+            || outside(|c| c.x >= -c.w)
+            || outside(|c| c.x <= c.w)
+            || outside(|c| c.y >= -c.w)
+            || outside(|c| c.y <= c.w))
+    }
+
+    /// Returns roughly how many pixels `bounds` covers on screen after being transformed by
+    /// `transform` into world space, using the bounding sphere of its corners.
+    /// Returns infinity if the camera is inside of the sphere.
+    pub fn projected_area(&self, bounds: &AABB3, transform: Mat4) -> f32 {
+        let corners = bounds.corners().map(|corner| transform.transform_point3(corner));
+        let center = corners.iter().fold(Vec3::zero(), |sum, c| sum + *c) / 8.;
+        let radius = corners.iter().map(|c| (*c - center).mag()).fold(0., f32::max);
+
+        let radius_pixels = match self.orthographic_height {
+            Some(height) => radius / height * self.screen_height,
+            None => {
+                let distance = (self.get_local_space_transform().transform_vec(center)).mag();
+                if distance <= radius {
+                    return f32::INFINITY;
+                }
+                radius / (distance * (self.vertical_fov / 2.).tan()) * self.screen_height / 2.
+            }
+        };
+        std::f32::consts::PI * radius_pixels * radius_pixels
+    }
+
+    /// Returns whether a triangle in clip space, as output by a [`VertexShader`], faces towards
+    /// the camera.
+    // ? https://www.cs.unc.edu/~olano/papers/2dh-tri/
+    pub fn is_clip_triangle_front_facing(&self, positions: [Vec4; 3], front_face: Winding) -> bool {
+        let [a, b, c] = positions.map(|p| Vec3::new(p.x, p.y, p.w));
+        // * The projection mirrors x, which makes the determinant positive for counter-clockwise
+        // * triangles facing the camera.
+        let det = Mat3::new(a, b, c).determinant();
+        match front_face {
+            Winding::CounterClockwise => det > 0.,
+            Winding::Clockwise => det < 0.,
+        }
+    }
+
+    /// Returns the [`Uniforms`] for rendering `obj` with this camera, where `transform` takes
+    /// the object from its local space into world space, see
+    /// [`SceneGraph::world_objects`](crate::scene_graph::SceneGraph::world_objects).
+    pub fn uniforms<'a>(&self, obj: &'a Object, transform: Mat4, lighting: &'a Lighting<'a>) -> Uniforms<'a> {
+        Uniforms {
+            model: transform,
+            normal_matrix: normal_matrix(transform),
+            view_projection: self.projection_matrix * self.get_local_space_transform().into_homogeneous_matrix(),
+            camera_position: self.position,
+            material: &obj.material,
+            lighting,
+        }
+    }
+
+    /// Runs `shader` on every triangle of `obj`, then clips and projects them, calling `f` with
+    /// each resulting screen space triangle. See [`Camera::shade_object`] for which triangles are skipped.
+    pub(crate) fn project_object<S, F>(&self, obj: &Object, uniforms: &Uniforms, shader: &S, mut f: F)
+    where
+        S: VertexShader,
+        F: FnMut(&[ScreenVertex<S::Varying>; 3]),
+    {
+        self.shade_object(obj, uniforms, shader, |clip_tri| {
+            for clipped_tri in clip_triangle(*clip_tri) {
+                f(&self.clip_to_screen(clipped_tri));
+            }
+        });
+    }
+
+    /// Runs `shader` on every triangle of `obj`, calling `f` with each triangle in clip space,
+    /// before it's clipped.
+    ///
+    /// Objects whose bounding box is outside of the view volume are skipped entirely, as are
+    /// back-facing triangles if the object has [`Object::cull_back_faces`] set. The bounding box
+    /// is checked before the shader runs, so shaders shouldn't move vertices far outside of it.
+    pub(crate) fn shade_object<S, F>(&self, obj: &Object, uniforms: &Uniforms, shader: &S, mut f: F)
+    where
+        S: VertexShader,
+        F: FnMut(&[ClipVertex<S::Varying>; 3]),
+    {
+        match obj.local_bounds() {
+            Some(bounds) if self.is_box_visible(&bounds, uniforms.model) => {}
+            _ => return,
+        }
+
+        // * Mirroring the object also flips the winding order of its triangles.
+        let front_face = if uniforms.model.truncate().determinant() < 0. {
+            obj.front_face.reversed()
+        } else {
+            obj.front_face
+        };
+
+        for (tri_index, tri_indices) in obj.triangles.iter().enumerate() {
+            let normal = obj.get_triangle_unchecked(*tri_indices).unit_normal();
+            let face_normal = match obj.front_face {
+                Winding::CounterClockwise => normal,
+                Winding::Clockwise => -normal,
+            };
+
+            let attributes = obj.get_vertex_attributes(tri_index);
+            let clip_tri = [0, 1, 2].map(|corner| {
+                let vertex = VertexInput {
+                    attributes: attributes[corner],
+                    face_normal,
+                    corner,
+                    triangle: tri_index,
+                };
+                let (position, varying) = shader.shade_vertex(uniforms, &vertex);
+                ClipVertex::new(position, varying)
+            });
+
+            if obj.cull_back_faces && !self.is_clip_triangle_front_facing(clip_tri.map(|v| v.position), front_face) {
+                continue;
+            }
+            f(&clip_tri);
+        }
+    }
+
+    /// Transforms clipped vertices, such as those of a triangle, from clip space to pixel
+    /// coordinates, keeping each vertex's depth and `1 / w` alongside its varyings for the rasterizer.
+    pub(crate) fn clip_to_screen<V: Varying, const N: usize>(&self, vertices: [ClipVertex<V>; N]) -> [ScreenVertex<V>; N] {
+        vertices.map(|v| {
+            let screen = self.clip_to_screen_point(v.position);
+            ScreenVertex {
+                position: screen.truncated(),
+                depth: screen.z,
+                inv_w: 1. / v.position.w,
+                varying: v.varying,
+            }
+        })
+    }
+
+    /// Moves a clip space position `fraction` of its distance in view space towards the camera,
+    /// keeping it at the same place on screen. Unlike offsetting its depth, this moves positions
+    /// far away from the camera just as far in front of the surfaces they're on as near ones.
+    pub fn pull_towards_camera(&self, position: Vec4, fraction: f32) -> Vec4 {
+        // * The projection is linear apart from this constant term, so scaling the rest of the
+        // * position scales the view space position along with it.
+        let offset = self.projection_matrix.cols[3];
+        let scale = 1. - fraction;
+        match self.orthographic_height {
+            // * Orthographic views look along parallel rays, so only the depth changes.
+            Some(_) => Vec4::new(position.x, position.y, (position.z - offset.z) * scale + offset.z, position.w),
+            None => (position - offset) * scale + offset,
+        }
+    }
+
+    /// Projects a world space position to pixel coordinates, keeping its depth in the z component.
+    /// Returns `None` if the position is outside of the view volume.
+    pub fn world_to_screen(&self, position: Vec3) -> Option<Vec3> {
+        let local = self.get_local_space_transform().transform_vec(position);
+        let clip = self.projection_matrix * local.into_homogeneous_point();
+        if clip.w <= 0. || clip.z < 0. || clip.z > clip.w {
+            return None;
+        }
+        Some(self.clip_to_screen_point(clip))
+    }
+
+    /// Performs the perspective divide on a clip space position and maps it to pixel
+    /// coordinates, keeping its depth in the z component.
+    pub fn clip_to_screen_point(&self, position: Vec4) -> Vec3 {
+        let ndc = position.truncated() / position.w;
+        Vec3::new(
+            self.screen_width / 2. * (ndc.x + 1.),
+            self.screen_height / 2. * (ndc.y + 1.),
+            ndc.z,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        object::tests::cube,
+        shader::{DepthShader, Lighting},
+    };
+
+    fn camera() -> Camera {
+        Camera::new(Vec3::new(0., 0., -5.), Rotor3::identity(), 1.5, 0.01, 1000., 64, 48)
+    }
+
+    /// A triangle at the origin, counter-clockwise as seen from the camera if `towards_camera`.
+    fn triangle(towards_camera: bool, front_face: Winding) -> Object {
+        Object {
+            vertices: vec![Vec3::zero(), Vec3::unit_y(), Vec3::unit_x()],
+            triangles: vec![if towards_camera { [0, 1, 2] } else { [0, 2, 1] }],
+            cull_back_faces: true,
+            front_face,
+            ..Object::new("triangle")
+        }
+    }
+
+    /// Returns how many of the object's triangles are kept by [`Camera::shade_object`].
+    fn shaded_triangles(camera: &Camera, obj: &Object, transform: Mat4) -> usize {
+        let lighting = Lighting::default();
+        let uniforms = camera.uniforms(obj, transform, &lighting);
+        let mut count = 0;
+        camera.shade_object(obj, &uniforms, &DepthShader, |_| count += 1);
+        count
+    }
+
+    #[test]
+    fn culls_back_faces_in_both_windings() {
+        let camera = camera();
+        for (towards_camera, front_face, kept) in [
+            (true, Winding::CounterClockwise, 1),
+            (false, Winding::CounterClockwise, 0),
+            (true, Winding::Clockwise, 0),
+            (false, Winding::Clockwise, 1),
+        ] {
+            let obj = triangle(towards_camera, front_face);
+            assert_eq!(shaded_triangles(&camera, &obj, Mat4::identity()), kept, "{:?}", front_face);
+
+            let mut unculled = obj.clone();
+            unculled.cull_back_faces = false;
+            assert_eq!(shaded_triangles(&camera, &unculled, Mat4::identity()), 1);
+        }
+    }
+
+    #[test]
+    fn mirrored_transform_keeps_front_faces() {
+        let camera = camera();
+        let obj = triangle(true, Winding::CounterClockwise);
+
+        // * Mirroring along x reverses the winding on screen, but the triangle still faces the camera.
+        let mirrored_x = Mat4::from_nonuniform_scale(Vec3::new(-1., 1., 1.));
+        assert_eq!(shaded_triangles(&camera, &obj, mirrored_x), 1);
+        // * Mirroring along z turns it around.
+        let mirrored_z = Mat4::from_nonuniform_scale(Vec3::new(1., 1., -1.));
+        assert_eq!(shaded_triangles(&camera, &obj, mirrored_z), 0);
+
+        let back = triangle(false, Winding::CounterClockwise);
+        assert_eq!(shaded_triangles(&camera, &back, mirrored_x), 0);
+        assert_eq!(shaded_triangles(&camera, &back, mirrored_z), 1);
+    }
+
+    #[test]
+    fn culls_boxes_outside_view_volume() {
+        let camera = camera();
+        let bounds = cube().local_bounds().unwrap();
+        let at = |x: f32, y: f32, z: f32| Mat4::from_translation(Vec3::new(x, y, z));
+
+        assert!(camera.is_box_visible(&bounds, Mat4::identity()));
+        // * Only partly inside of one of the side planes.
+        assert!(camera.is_box_visible(&bounds, at(-6.5, 0., 0.)));
+        // * Behind the camera, which is at z = -5.
+        assert!(!camera.is_box_visible(&bounds, at(0., 0., -10.)));
+        // * Beside the camera, straddling its near plane.
+        assert!(!camera.is_box_visible(&bounds, at(3., 0., -5.5)));
+        // * Off to one side, or beyond the far plane.
+        assert!(!camera.is_box_visible(&bounds, at(100., 0., 0.)));
+        assert!(!camera.is_box_visible(&bounds, at(0., -100., 0.)));
+        assert!(!camera.is_box_visible(&bounds, at(0., 0., 2000.)));
+
+        let mut obj = cube();
+        obj.cull_back_faces = false;
+        assert_eq!(shaded_triangles(&camera, &obj, Mat4::identity()), 12);
+        assert_eq!(shaded_triangles(&camera, &obj, at(0., 0., -10.)), 0);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::shader::Varying;
+use ultraviolet::Vec4;
+
+/// A vertex in homogeneous clip space, before the perspective divide.
+#[derive(Debug, Clone, Copy)]
+pub struct ClipVertex<V> {
+    pub position: Vec4,
+    pub varying: V,
+}
+
+impl<V: Varying> ClipVertex<V> {
+    pub fn new(position: Vec4, varying: V) -> Self {
+        Self { position, varying }
+    }
+
+    /// Linearly interpolates between `self` and `other`, which is valid for
+    /// any varying as long as it happens before the perspective divide.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            position: self.position + (other.position - self.position) * t,
+            varying: self.varying.lerp(other.varying, t),
+        }
+    }
+}
+
+/// The six planes bounding the view volume (`-w <= x <= w`, `-w <= y <= w`, `0 <= z <= w`).
+/// A clip-space position is on the inside of a plane when their dot product is non-negative.
+fn clip_planes() -> [Vec4; 6] {
+    [
+        Vec4::new(0., 0., 1., 0.),
+        Vec4::new(0., 0., -1., 1.),
+        Vec4::new(1., 0., 0., 1.),
+        Vec4::new(-1., 0., 0., 1.),
+        Vec4::new(0., 1., 0., 1.),
+        Vec4::new(0., -1., 0., 1.),
+    ]
+}
+
+/// Clips a triangle against the view volume, splitting it into smaller triangles where it is only
+/// partially visible. Returns an empty `Vec` if no part of the triangle is visible.
+// ? Sutherland–Hodgman: https://en.wikipedia.org/wiki/Sutherland%E2%80%93Hodgman_algorithm
+pub fn clip_triangle<V: Varying>(triangle: [ClipVertex<V>; 3]) -> Vec<[ClipVertex<V>; 3]> {
+    let planes = clip_planes();
+
+    if triangle.iter().all(|v| is_inside(v.position)) {
+        return vec![triangle];
+    }
+
+    let mut polygon = triangle.to_vec();
+    for plane in planes {
+        let prev_polygon = std::mem::take(&mut polygon);
+
+        for (i, current) in prev_polygon.iter().enumerate() {
+            let next = &prev_polygon[(i + 1) % prev_polygon.len()];
+            let d_current = plane.dot(current.position);
+            let d_next = plane.dot(next.position);
+
+            if d_current >= 0. {
+                polygon.push(*current);
+            }
+            if (d_current >= 0.) != (d_next >= 0.) {
+                polygon.push(current.lerp(next, d_current / (d_current - d_next)));
+            }
+        }
+
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    (1..polygon.len() - 1)
+        .map(|i| [polygon[0], polygon[i], polygon[i + 1]])
+        .collect()
+}
+
+/// Returns whether a clip space position is inside of the view volume.
+pub fn is_inside(position: Vec4) -> bool {
+    clip_planes().iter().all(|plane| plane.dot(position) >= 0.)
+}
+
+/// Clips a line against the view volume, shortening it to the part that is visible.
+/// Returns `None` if no part of the line is visible.
+// ? Liang–Barsky: https://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm
+pub fn clip_line<V: Varying>(line: [ClipVertex<V>; 2]) -> Option<[ClipVertex<V>; 2]> {
+    let [start, end] = line;
+    let (mut t_start, mut t_end) = (0f32, 1f32);
+    for plane in clip_planes() {
+        let d_start = plane.dot(start.position);
+        let d_end = plane.dot(end.position);
+        match (d_start >= 0., d_end >= 0.) {
+            (true, true) => {}
+            (false, false) => return None,
+            (true, false) => t_end = t_end.min(d_start / (d_start - d_end)),
+            (false, true) => t_start = t_start.max(d_start / (d_start - d_end)),
+        }
+    }
+
+    (t_start <= t_end).then(|| [start.lerp(&end, t_start), start.lerp(&end, t_end)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A vertex whose varying is its own position, so that interpolated varyings can be checked
+    /// against where they ended up.
+    fn vertex(x: f32, y: f32, z: f32, w: f32) -> ClipVertex<Vec4> {
+        let position = Vec4::new(x, y, z, w);
+        ClipVertex::new(position, position)
+    }
+
+    #[test]
+    fn keeps_triangle_inside() {
+        let triangle = [vertex(-0.5, -0.5, 0.5, 1.), vertex(0.5, -0.5, 0.5, 1.), vertex(0., 0.5, 0.5, 1.)];
+        let clipped = clip_triangle(triangle);
+
+        assert_eq!(clipped.len(), 1);
+        for (clipped, original) in clipped[0].iter().zip(triangle) {
+            assert_eq!(clipped.position, original.position);
+            assert_eq!(clipped.varying, original.varying);
+        }
+    }
+
+    #[test]
+    fn drops_triangle_outside() {
+        // * Every corner is beyond the right plane, though the triangle spans the whole depth range.
+        let triangle = [vertex(2., 0., -1., 1.), vertex(3., 0.5, 0.5, 1.), vertex(2., -0.5, 2., 1.)];
+        assert!(clip_triangle(triangle).is_empty());
+    }
+
+    #[test]
+    fn splits_triangle_crossing_near_plane() {
+        // * One corner is behind the near plane, so the triangle becomes a quad split in two.
+        let triangle = [vertex(-0.5, -0.5, 0.5, 1.), vertex(0.5, -0.5, 0.5, 1.), vertex(0., 0.5, -0.5, 1.)];
+        let clipped = clip_triangle(triangle);
+
+        assert_eq!(clipped.len(), 2);
+        let vertices = clipped.iter().flatten().collect::<Vec<_>>();
+        for v in &vertices {
+            assert!(is_inside(v.position));
+            assert!((v.varying - v.position).mag() < 1e-6);
+        }
+
+        let on_plane = vertices.iter().filter(|v| v.position.z.abs() < 1e-6).collect::<Vec<_>>();
+        assert!(!on_plane.is_empty());
+        for v in on_plane {
+            assert!(v.varying.z.abs() < 1e-6);
+            // * Halfway along both edges leading to the clipped corner.
+            assert!(v.varying.y.abs() < 1e-6);
+            assert!((v.varying.x.abs() - 0.25).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn shortens_line_crossing_planes() {
+        let line = [vertex(-2., 0., 0.5, 1.), vertex(0.5, 0., -0.5, 1.)];
+        let [start, end] = clip_line(line).unwrap();
+
+        // * Enters through the left plane at x = -w and leaves through the near plane at z = 0.
+        assert!((start.position - Vec4::new(-1., 0., 0.1, 1.)).mag() < 1e-6);
+        assert!((end.position - Vec4::new(-0.75, 0., 0., 1.)).mag() < 1e-6);
+        for v in [start, end] {
+            assert!((v.varying - v.position).mag() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn keeps_line_inside() {
+        let line = [vertex(-0.5, 0.2, 0.1, 1.), vertex(0.5, -0.2, 0.9, 1.)];
+        let [start, end] = clip_line(line).unwrap();
+        assert_eq!(start.position, line[0].position);
+        assert_eq!(end.position, line[1].position);
+    }
+
+    #[test]
+    fn rejects_line_outside() {
+        // * Both ends are outside of the same plane.
+        assert!(clip_line([vertex(0., 2., 0.5, 1.), vertex(0.5, 3., 0.5, 1.)]).is_none());
+        // * Crosses the left and top planes, but only beyond the corner of the view volume.
+        assert!(clip_line([vertex(-3., 0.5, 0.5, 1.), vertex(-0.5, 3., 0.5, 1.)]).is_none());
+    }
+}
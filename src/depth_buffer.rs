@@ -0,0 +1,100 @@
+use crate::camera::Camera;
+
+/// How a new depth is compared against the one already in a [`DepthBuffer`] to decide whether
+/// it's drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthCompare {
+    /// Passes if the new depth is smaller, for forward Z where the near plane is at 0.
+    Less,
+    /// Passes if the new depth is larger, for reversed Z where the near plane is at 1.
+    Greater,
+    /// Passes if the new depth is exactly the same, such as when redrawing the same triangles
+    /// after a depth pre-pass.
+    Equal,
+    /// Always passes, drawing everything in order.
+    Always,
+}
+
+impl DepthCompare {
+    /// Returns whether `depth` passes the test against the `stored` depth.
+    pub fn passes(self, depth: f32, stored: f32) -> bool {
+        match self {
+            DepthCompare::Less => depth < stored,
+            DepthCompare::Greater => depth > stored,
+            DepthCompare::Equal => depth == stored,
+            DepthCompare::Always => true,
+        }
+    }
+}
+
+/// The depth of every pixel drawn so far, along with how it's cleared and tested. Keep it
+/// around between frames to avoid reallocating it, like a [`FrameBuffer`](crate::framebuffer::FrameBuffer).
+///
+/// The clear value and compare function have to match the depth range of the
+/// [`Camera`] that is rendered with, see [`DepthBuffer::for_camera`] and [`DepthBuffer::match_camera`].
+#[derive(Debug, Clone)]
+pub struct DepthBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+    /// The depth that [`DepthBuffer::clear`] resets every pixel to, which should be the far plane.
+    pub clear_value: f32,
+    pub compare: DepthCompare,
+}
+
+impl DepthBuffer {
+    pub fn new(width: u32, height: u32, clear_value: f32, compare: DepthCompare) -> Self {
+        Self {
+            width,
+            height,
+            values: vec![clear_value; width as usize * height as usize],
+            clear_value,
+            compare,
+        }
+    }
+
+    /// Creates a buffer covering the camera's screen, cleared to its far plane and keeping
+    /// whatever is nearest to it.
+    pub fn for_camera(camera: &Camera) -> Self {
+        let (clear_value, compare) = Self::depth_range(camera);
+        Self::new(camera.screen_width as u32, camera.screen_height as u32, clear_value, compare)
+    }
+
+    /// Sets the clear value and compare function to those of [`DepthBuffer::for_camera`], for
+    /// when [`Camera::reversed_z`] might have changed. The stored depths are left as they are.
+    pub fn match_camera(&mut self, camera: &Camera) {
+        (self.clear_value, self.compare) = Self::depth_range(camera);
+    }
+
+    /// Returns the far plane and the compare function keeping the nearest depth for `camera`.
+    fn depth_range(camera: &Camera) -> (f32, DepthCompare) {
+        if camera.reversed_z {
+            (0., DepthCompare::Greater)
+        } else {
+            (1., DepthCompare::Less)
+        }
+    }
+
+    /// Resizes the buffer, clearing it if the dimensions changed.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.width != width || self.height != height {
+            self.width = width;
+            self.height = height;
+            self.values = vec![self.clear_value; width as usize * height as usize];
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(self.clear_value);
+    }
+
+    /// Returns whether `depth` passes the depth test at the pixel index `idx`.
+    pub fn passes(&self, idx: usize, depth: f32) -> bool {
+        self.compare.passes(depth, self.values[idx])
+    }
+
+    /// Returns whether anything has been drawn at the pixel index `idx` since it was last cleared.
+    pub fn is_drawn(&self, idx: usize) -> bool {
+        self.values[idx] != self.clear_value
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// The kinds of files that assets are loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetFormat {
+    Stl,
+    Ply,
+    Obj,
+    Mtl,
+    Gltf,
+    Texture,
+}
+
+impl fmt::Display for AssetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AssetFormat::Stl => "STL",
+            AssetFormat::Ply => "PLY",
+            AssetFormat::Obj => "OBJ",
+            AssetFormat::Mtl => "MTL",
+            AssetFormat::Gltf => "glTF",
+            AssetFormat::Texture => "texture",
+        })
+    }
+}
+
+/// An error that happened while loading an asset, along with the file it happened in.
+#[derive(Debug)]
+pub enum AssetError {
+    /// The file couldn't be opened or read.
+    Io {
+        path: PathBuf,
+        format: AssetFormat,
+        source: io::Error,
+    },
+    /// The file was read, but its contents aren't valid.
+    Invalid {
+        path: PathBuf,
+        format: AssetFormat,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A texture used by a material couldn't be opened.
+    MissingTexture { path: PathBuf, source: io::Error },
+    /// A texture used by a material isn't an image that can be decoded.
+    UndecodableTexture { path: PathBuf, source: image::ImageError },
+}
+
+impl AssetError {
+    /// Wraps an error from reading `path`, where parsers report invalid contents
+    /// as [`ErrorKind::InvalidData`] or [`ErrorKind::UnexpectedEof`].
+    pub(crate) fn from_io(path: impl AsRef<Path>, format: AssetFormat, source: io::Error) -> Self {
+        let path = path.as_ref().to_owned();
+        match source.kind() {
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof => AssetError::Invalid {
+                path,
+                format,
+                source: Box::new(source),
+            },
+            _ => AssetError::Io { path, format, source },
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            AssetError::Io { path, .. }
+            | AssetError::Invalid { path, .. }
+            | AssetError::MissingTexture { path, .. }
+            | AssetError::UndecodableTexture { path, .. } => path,
+        }
+    }
+
+    pub fn format(&self) -> AssetFormat {
+        match self {
+            AssetError::Io { format, .. } | AssetError::Invalid { format, .. } => *format,
+            AssetError::MissingTexture { .. } | AssetError::UndecodableTexture { .. } => {
+                AssetFormat::Texture
+            }
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (path, format) = (self.path().display(), self.format());
+        match self {
+            AssetError::Io { source, .. } => write!(f, "unable to read {} file `{}`: {}", format, path, source),
+            AssetError::Invalid { source, .. } => write!(f, "invalid {} file `{}`: {}", format, path, source),
+            AssetError::MissingTexture { source, .. } => write!(f, "missing texture `{}`: {}", path, source),
+            AssetError::UndecodableTexture { source, .. } => {
+                write!(f, "unable to decode texture `{}`: {}", path, source)
+            }
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AssetError::Io { source, .. } | AssetError::MissingTexture { source, .. } => Some(source),
+            AssetError::Invalid { source, .. } => Some(source.as_ref()),
+            AssetError::UndecodableTexture { source, .. } => Some(source),
+        }
+    }
+}
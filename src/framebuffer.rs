@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use image::{ImageResult, RgbImage};
+use ultraviolet::Vec3;
+
+/// Packs an RGB colour into the `0RGB` format used by softbuffer.
+pub fn combine_rgb(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+/// Packs a colour with channels in the range `0..=1` into the `0RGB` format, clamping any
+/// channels outside of that range.
+pub fn color_to_rgb(color: Vec3) -> u32 {
+    let color = color.clamped(Vec3::zero(), Vec3::one()) * 255.;
+    combine_rgb(color.x as u8, color.y as u8, color.z as u8)
+}
+
+/// Unpacks a `0RGB` colour into its separate channels.
+pub fn split_rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
+
+/// A window-independent colour buffer that [`Scene::render`](crate::scene::Scene::render) draws into.
+///
+/// Pixels are stored row by row in the same `0RGB` format as a `softbuffer::Buffer`,
+/// so the viewer can copy a frame straight to the screen, while headless renders
+/// can be saved through the `image` crate instead.
+pub struct FrameBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u32>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize],
+        }
+    }
+
+    /// Resizes the buffer, discarding its contents if the dimensions changed.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.width != width || self.height != height {
+            self.width = width;
+            self.height = height;
+            self.pixels = vec![0; width as usize * height as usize];
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.pixels.fill(color);
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            image::Rgb(split_rgb(self.pixels[y as usize * self.width as usize + x as usize]))
+        })
+    }
+
+    /// Saves the buffer to `path`, with the image format chosen from its extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.to_image().save(path)
+    }
+}
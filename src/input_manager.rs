@@ -1,15 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use ultraviolet::Vec2;
 use winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, KeyboardInput, MouseButton, ScanCode, VirtualKeyCode},
+    event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode},
     window::CursorGrabMode,
 };
 
 pub struct InputManager {
     held_keycodes: HashMap<VirtualKeyCode, bool>,
-    held_scancodes: HashMap<ScanCode, bool>,
+    /// Keys that were pressed since they were last checked by [`InputManager::use_keycode_press`].
+    pressed_keycodes: HashSet<VirtualKeyCode>,
     held_mouse_buttons: HashMap<MouseButton, bool>,
     cursor_pos: Vec2,
     mouse_delta: Vec2,
@@ -18,11 +19,17 @@ pub struct InputManager {
     pub cursor_visible: bool,
 }
 
+impl Default for InputManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InputManager {
     pub fn new() -> Self {
         Self {
             held_keycodes: HashMap::new(),
-            held_scancodes: HashMap::new(),
+            pressed_keycodes: HashSet::new(),
             held_mouse_buttons: HashMap::new(),
             cursor_pos: Vec2::zero(),
             mouse_delta: Vec2::zero(),
@@ -35,21 +42,16 @@ impl InputManager {
         if let Some(keycode) = input.virtual_keycode {
             match input.state {
                 winit::event::ElementState::Pressed => {
+                    // * Held keys repeat their press events, which shouldn't count as new presses.
+                    if !self.is_keycode_held(keycode) {
+                        self.pressed_keycodes.insert(keycode);
+                    }
                     self.held_keycodes.insert(keycode, true);
                 }
                 winit::event::ElementState::Released => {
                     self.held_keycodes.insert(keycode, false);
                 }
             }
-        } else {
-            match input.state {
-                winit::event::ElementState::Pressed => {
-                    self.held_scancodes.insert(input.scancode, true);
-                }
-                winit::event::ElementState::Released => {
-                    self.held_scancodes.insert(input.scancode, false);
-                }
-            }
         }
     }
 
@@ -74,25 +76,22 @@ impl InputManager {
 
     pub fn is_keycode_held(&self, code: VirtualKeyCode) -> bool {
         if let Some(held) = self.held_keycodes.get(&code) {
-            return *held;
+            *held
         } else {
-            return false;
+            false
         }
     }
 
-    pub fn is_scancode_held(&self, code: ScanCode) -> bool {
-        if let Some(held) = self.held_scancodes.get(&code) {
-            return *held;
-        } else {
-            return false;
-        }
+    /// Returns whether the key was pressed since the last time this was called for it.
+    pub fn use_keycode_press(&mut self, code: VirtualKeyCode) -> bool {
+        self.pressed_keycodes.remove(&code)
     }
 
     pub fn is_mouse_button_held(&self, button: MouseButton) -> bool {
         if let Some(held) = self.held_mouse_buttons.get(&button) {
-            return *held;
+            *held
         } else {
-            return false;
+            false
         }
     }
 
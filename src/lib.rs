@@ -0,0 +1,39 @@
+//! A CPU rasterizer for rendering triangle meshes loaded from .stl, .obj, .ply and glTF files.
+//!
+//! Build a [`SceneGraph`] out of [`Object`]s, wrap it in a [`Scene`] along with a [`Camera`]
+//! and lights, and render it into a [`FrameBuffer`] with [`Scene::render`].
+
+mod aabb;
+pub mod camera;
+mod clipping;
+pub mod depth_buffer;
+pub mod error;
+pub mod framebuffer;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod object;
+mod ply;
+mod rasterizer;
+pub mod scene;
+pub mod scene_graph;
+pub mod shader;
+pub mod shadow;
+mod simplify;
+mod subdivide;
+pub mod texture;
+mod triangle;
+pub mod validation;
+pub mod vertex;
+
+pub use aabb::AABB3;
+pub use camera::Camera;
+pub use depth_buffer::{DepthBuffer, DepthCompare};
+pub use error::{AssetError, AssetFormat};
+pub use framebuffer::FrameBuffer;
+pub use light::Light;
+pub use material::Material;
+pub use object::Object;
+pub use scene::Scene;
+pub use scene_graph::{NodeId, SceneGraph};
+pub use triangle::{Triangle3D, Winding};
@@ -0,0 +1,112 @@
+use ultraviolet::Vec3;
+
+/// Light arriving at a surface point.
+#[derive(Debug, Clone, Copy)]
+pub struct IncidentLight {
+    /// Unit vector pointing from the surface towards the light.
+    pub direction: Vec3,
+    pub radiance: Vec3,
+}
+
+/// How a light's intensity falls off over distance: `1 / (constant + linear * d + quadratic * d^2)`.
+// ? https://learnopengl.com/Lighting/Light-casters
+#[derive(Debug, Clone, Copy)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self {
+            constant: 1.,
+            linear: 0.09,
+            quadratic: 0.032,
+        }
+    }
+}
+
+impl Attenuation {
+    pub fn factor(&self, distance: f32) -> f32 {
+        1. / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    /// A light infinitely far away, such as the sun, shining along `direction`.
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        casts_shadows: bool,
+    },
+    /// A light shining equally in all directions from `position`.
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    /// A light shining from `position` along `direction`, in a cone that is fully lit up to
+    /// `inner_angle` and fades out until `outer_angle`. Both angles are in radians, measured
+    /// from `direction`.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+        casts_shadows: bool,
+    },
+}
+
+impl Light {
+    /// Returns the light arriving at the world space `position`, or `None` if it isn't lit.
+    pub fn illuminate(&self, position: Vec3) -> Option<IncidentLight> {
+        match *self {
+            Light::Directional { direction, color, intensity, .. } => Some(IncidentLight {
+                direction: -direction.normalized(),
+                radiance: color * intensity,
+            }),
+            Light::Point { position: light_position, color, intensity, attenuation } => {
+                let to_light = light_position - position;
+                let distance = to_light.mag();
+                Some(IncidentLight {
+                    direction: to_light / distance,
+                    radiance: color * intensity * attenuation.factor(distance),
+                })
+            }
+            Light::Spot {
+                position: light_position,
+                direction,
+                color,
+                intensity,
+                attenuation,
+                inner_angle,
+                outer_angle,
+                ..
+            } => {
+                let to_light = light_position - position;
+                let distance = to_light.mag();
+                let to_light = to_light / distance;
+
+                // * Smoothly fade out between the cosines of the inner and outer angles.
+                let cos_angle = (-to_light).dot(direction.normalized());
+                let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+                let cone = ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(f32::EPSILON)).clamp(0., 1.);
+                if cone <= 0. {
+                    return None;
+                }
+
+                Some(IncidentLight {
+                    direction: to_light,
+                    radiance: color * intensity * attenuation.factor(distance) * cone,
+                })
+            }
+        }
+    }
+}
@@ -1,29 +1,44 @@
-//   black - combine_rgb(  0,   0,   0)
-//    grey - combine_rgb(100, 100, 100)
-//   white - combine_rgb(255, 255, 255)
-//     red - combine_rgb(255,   0,   0)
-//   green - combine_rgb(  0, 255,   0)
-//    blue - combine_rgb(  0,   0, 255)
-//  yellow - combine_rgb(255, 255,   0)
-// magenta - combine_rgb(255,   0, 255)
-//    teal - combine_rgb(  0, 255, 255)
-
-mod aabb;
-mod camera;
+
+
+//! The DeDeDe viewer, which renders a scene in a window, or a single frame of a model
+//! to an image with `--headless`.
+
 mod input_manager;
-mod object;
-mod scene;
-mod triangle;
 
-use object::Object;
-use ultraviolet::{Rotor3, Vec3};
-use std::{num::NonZeroU32, f32::consts::PI};
+use dedede::{AssetError, FrameBuffer, Object, Scene, SceneGraph};
+use input_manager::InputManager;
+use std::{num::NonZeroU32, time::Instant};
+use ultraviolet::Vec3;
 use winit::{
-    event::{Event, WindowEvent, DeviceEvent},
-    window::{Fullscreen, WindowBuilder, CursorGrabMode},
+    event::{DeviceEvent, Event, MouseButton, VirtualKeyCode, WindowEvent},
+    window::{CursorGrabMode, Fullscreen, Window, WindowBuilder},
 };
 
+/// How many simplified versions of each loaded object to generate, see [`Object::generate_lods`].
+const LOD_LEVELS: usize = 3;
+
+/// How far the camera turns for each pixel the mouse moves, in radians.
+const MOUSE_SENSITIVITY: f32 = 0.004;
+
+const HEADLESS_USAGE: &str = "Usage: dedede --headless <model.stl|model.obj|model.ply|model.gltf|model.glb> <output.png> [width] [height]";
+
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("--headless") {
+        let (Some(model_path), Some(output_path)) = (args.get(2), args.get(3)) else {
+            eprintln!("{}", HEADLESS_USAGE);
+            std::process::exit(1);
+        };
+        let width = args.get(4).map_or(Some(800), |w| w.parse().ok());
+        let height = args.get(5).map_or(Some(600), |h| h.parse().ok());
+        let (Some(width), Some(height)) = (width, height) else {
+            eprintln!("{}", HEADLESS_USAGE);
+            std::process::exit(1);
+        };
+        render_headless(model_path, output_path, width, height);
+        return;
+    }
+
     let event_loop = winit::event_loop::EventLoop::new();
     let mut window = WindowBuilder::new()
         .with_active(true)
@@ -36,7 +51,7 @@ fn main() {
     let context = unsafe { softbuffer::Context::new(&window) }.unwrap();
     let mut surface = unsafe { softbuffer::Surface::new(&context, &window) }.unwrap();
 
-    // let mut scene = scene::Scene::new(
+    // let mut scene = Scene::new(
     //     vec![Object::load_from_stl("test models/basics/suzanne_hd.stl").unwrap()],
     //     window.inner_size().width,
     //     window.inner_size().height,
@@ -49,7 +64,7 @@ fn main() {
     //     suzanne_hd.position += 3. * Vec3::unit_y();
     // let mut torus = Object::load_from_stl("test models/basics/torus.stl").unwrap();
     //     torus.position += 3. * Vec3::unit_x();
-    let mut cube = Object::load_from_stl("test models/basics/cube.stl").unwrap();
+    let cube = Object::load_from_stl("test models/basics/cube.stl").unwrap();
     //     cube.position -= 3. * Vec3::unit_x();
 
     // let triangle = Object {
@@ -63,24 +78,31 @@ fn main() {
     // };
 
     
-    let mut scene = scene::Scene::new(
+    let mut scene = Scene::new(
         vec![
             // suzanne_uv,
             // suzanne_hd,
             // torus,
             cube,
             // triangle,
-            ],
+            ].into(),
         window.inner_size().width,
         window.inner_size().height,
     );
+    // * The scene picks which LOD to render for each object every frame, by its size on screen.
+    generate_lods(&mut scene.graph);
+    let mut frame = FrameBuffer::new(window.inner_size().width, window.inner_size().height);
+    let mut input_manager = InputManager::new();
+    let mut prev_update_time = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
         control_flow.set_poll();
 
         match event {
             Event::MainEventsCleared | Event::RedrawRequested(_) => {
-                scene.update(&mut window);
+                let now = Instant::now();
+                let delta_time = (now - std::mem::replace(&mut prev_update_time, now)).as_millis() as f32;
+                update(&mut scene, &mut input_manager, &mut window, delta_time);
 
                 let (width, height) = (window.inner_size().width, window.inner_size().height);
                 surface
@@ -90,8 +112,11 @@ fn main() {
                     )
                     .unwrap();
 
+                frame.resize(width, height);
+                scene.render(&mut frame);
+
                 let mut buffer = surface.buffer_mut().unwrap();
-                scene.render(&mut buffer, width, height);
+                buffer.copy_from_slice(&frame.pixels);
                 buffer.present().unwrap();
             }
 
@@ -101,13 +126,13 @@ fn main() {
                         control_flow.set_exit();
                     }
                     WindowEvent::KeyboardInput { input, .. } => {
-                        scene.input_manager.handle_keyboard_input(input)
+                        input_manager.handle_keyboard_input(input)
                     }
                     WindowEvent::MouseInput { state, button, .. } => {
-                        scene.input_manager.handle_mouse_button(button, state)
+                        input_manager.handle_mouse_button(button, state)
                     }
                     WindowEvent::CursorMoved { position, .. } => {
-                        scene.input_manager.handle_cursor_movement(position)
+                        input_manager.handle_cursor_movement(position)
                     }
                     // WindowEvent::CursorEntered { device_id } => todo!(),
                     // WindowEvent::CursorLeft { device_id } => todo!(),
@@ -116,15 +141,93 @@ fn main() {
                 }
             }
 
-            Event::DeviceEvent { event, .. } => {
-                match event {
-                    DeviceEvent::MouseMotion { delta } => {
-                        scene.input_manager.handle_mouse_movement(delta);
-                    }
-                    _ => {}
-                }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                input_manager.handle_mouse_movement(delta);
             }
             _ => {}
         }
     });
 }
+
+/// Moves the camera and switches render modes according to the input since the last frame,
+/// `delta_time` milliseconds ago.
+fn update(scene: &mut Scene, input_manager: &mut InputManager, window: &mut Window, delta_time: f32) {
+    if input_manager.is_keycode_held(VirtualKeyCode::Escape) {
+        input_manager.cursor_visible = true;
+        input_manager.cursor_mode = CursorGrabMode::None;
+    } else if input_manager.is_mouse_button_held(MouseButton::Left) {
+        input_manager.cursor_visible = false;
+        input_manager.cursor_mode = CursorGrabMode::Locked;
+    }
+    window.set_cursor_visible(input_manager.cursor_visible);
+    window.set_cursor_grab(input_manager.cursor_mode).unwrap();
+
+    let movement_keys = [
+        (VirtualKeyCode::W, Vec3::unit_z()),
+        (VirtualKeyCode::S, -Vec3::unit_z()),
+        (VirtualKeyCode::A, Vec3::unit_x()),
+        (VirtualKeyCode::D, -Vec3::unit_x()),
+        (VirtualKeyCode::LControl, Vec3::unit_y()),
+        (VirtualKeyCode::Space, -Vec3::unit_y()),
+    ];
+    for (keycode, direction) in movement_keys {
+        if input_manager.is_keycode_held(keycode) {
+            scene.camera.move_local(0.001 * delta_time * direction);
+        }
+    }
+
+    if input_manager.use_keycode_press(VirtualKeyCode::M) {
+        scene.render_mode = scene.render_mode.next();
+    }
+    if input_manager.use_keycode_press(VirtualKeyCode::V) {
+        scene.debug_view = scene.debug_view.next();
+    }
+
+    if !input_manager.cursor_visible {
+        scene.camera.turn(MOUSE_SENSITIVITY * input_manager.use_mouse_delta().x);
+    }
+}
+
+/// Renders a single frame of the model at `model_path` without creating a window,
+/// and saves it to `output_path`.
+fn render_headless(model_path: &str, output_path: &str, width: u32, height: u32) {
+    let mut graph = load_model(model_path).unwrap_or_else(|e| {
+        eprintln!("Unable to load model: {}", e);
+        std::process::exit(1);
+    });
+
+    generate_lods(&mut graph);
+
+    let mut scene = Scene::new(graph, width, height);
+    let mut frame = FrameBuffer::new(width, height);
+    scene.render(&mut frame);
+    frame.save(output_path).expect("Unable to save render!");
+}
+
+/// Generates [`LOD_LEVELS`] simplified versions of every object in `graph`.
+fn generate_lods(graph: &mut SceneGraph) {
+    for id in graph.ids().collect::<Vec<_>>() {
+        graph.get_mut(id).generate_lods(LOD_LEVELS);
+    }
+}
+
+/// Loads a model into a new [`SceneGraph`], picking the loader from the file extension.
+fn load_model(model_path: &str) -> Result<SceneGraph, AssetError> {
+    if model_path.ends_with(".gltf") || model_path.ends_with(".glb") {
+        return SceneGraph::load_from_gltf(model_path);
+    }
+
+    let mut graph = SceneGraph::new();
+    if model_path.ends_with(".obj") {
+        // * Group the file's models under one node, so that they move together.
+        let root = graph.add(Object::new(model_path), None);
+        for obj in Object::load_many_from_obj(model_path)? {
+            graph.add(obj, Some(root));
+        }
+    } else if model_path.ends_with(".ply") {
+        graph.add(Object::load_from_ply(model_path)?, None);
+    } else {
+        graph.add(Object::load_from_stl(model_path)?, None);
+    }
+    Ok(graph)
+}
@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{error::AssetError, light::IncidentLight, texture::Texture, vertex::VertexAttributes};
+use image::RgbaImage;
+use ultraviolet::{Vec3, Vec4};
+
+/// Which terms are used to shade a [`Material`], as selected by the `illum` statement of a .mtl file.
+// ? https://paulbourke.net/dataformats/mtl/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IlluminationModel {
+    /// `illum 0`: Flat diffuse colour, without any lighting.
+    Constant,
+    /// `illum 1`: Ambient and Lambertian diffuse lighting.
+    #[default]
+    Lambert,
+    /// `illum 2` and above: Ambient, diffuse and Blinn-Phong specular lighting.
+    BlinnPhong,
+}
+
+impl From<u8> for IlluminationModel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => IlluminationModel::Constant,
+            1 => IlluminationModel::Lambert,
+            _ => IlluminationModel::BlinnPhong,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Material {
+    pub name: String,
+
+    /// `Ka`
+    pub ambient: Vec3,
+    /// `Kd`
+    pub diffuse: Vec3,
+    /// `Ks`
+    pub specular: Vec3,
+    /// `Ns`, the specular exponent.
+    pub shininess: f32,
+    /// `d`, where 1 is fully opaque.
+    pub dissolve: f32,
+    /// `illum`
+    pub illumination_model: IlluminationModel,
+
+    pub ambient_texture: Option<Texture>,
+    pub diffuse_texture: Option<Texture>,
+    pub specular_texture: Option<Texture>,
+    /// Loaded, but not used for shading yet, as that requires per-vertex tangents.
+    pub normal_texture: Option<Texture>,
+    pub shininess_texture: Option<Texture>,
+    pub dissolve_texture: Option<Texture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ambient: Vec3::one(),
+            diffuse: Vec3::one(),
+            specular: Vec3::zero(),
+            shininess: 0.,
+            dissolve: 1.,
+            illumination_model: IlluminationModel::default(),
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+            shininess_texture: None,
+            dissolve_texture: None,
+        }
+    }
+}
+
+impl Material {
+    /// Converts a material loaded by `tobj`, loading its texture maps relative to `dir`.
+    /// Properties missing from the .mtl file keep their [`Default`] values.
+    pub fn from_mtl(material: &tobj::Material, dir: &Path) -> Result<Self, AssetError> {
+        let default = Self::default();
+        let load_texture = |texture_path: &Option<String>| {
+            texture_path
+                .as_ref()
+                .map(|texture_path| Texture::load(&dir.join(texture_path)))
+                .transpose()
+        };
+
+        Ok(Self {
+            name: material.name.clone(),
+            ambient: material.ambient.map_or(default.ambient, Vec3::from),
+            diffuse: material.diffuse.map_or(default.diffuse, Vec3::from),
+            specular: material.specular.map_or(default.specular, Vec3::from),
+            shininess: material.shininess.unwrap_or(default.shininess),
+            dissolve: material.dissolve.unwrap_or(default.dissolve),
+            illumination_model: material
+                .illumination_model
+                .map_or(default.illumination_model, IlluminationModel::from),
+            ambient_texture: load_texture(&material.ambient_texture)?,
+            diffuse_texture: load_texture(&material.diffuse_texture)?,
+            specular_texture: load_texture(&material.specular_texture)?,
+            normal_texture: load_texture(&material.normal_texture)?,
+            shininess_texture: load_texture(&material.shininess_texture)?,
+            dissolve_texture: load_texture(&material.dissolve_texture)?,
+        })
+    }
+
+    /// Converts a glTF material, using the images loaded by [`Texture::load_gltf_image`]. Only
+    /// the base colour and its texture are used, as the metallic-roughness model isn't supported.
+    pub(crate) fn from_gltf(material: &gltf::Material, images: &[RgbaImage]) -> Self {
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, a] = pbr.base_color_factor();
+
+        Self {
+            name: material.name().unwrap_or_default().to_owned(),
+            diffuse: Vec3::new(r, g, b),
+            dissolve: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => 1.,
+                _ => a,
+            },
+            diffuse_texture: pbr
+                .base_color_texture()
+                .and_then(|info| Texture::from_gltf(&info.texture(), images)),
+            ..Self::default()
+        }
+    }
+
+    /// The name used for the material in .mtl files, where it can't be empty.
+    pub fn mtl_name(&self) -> &str {
+        if self.name.is_empty() {
+            "default"
+        } else {
+            &self.name
+        }
+    }
+
+    /// Writes the material to a .mtl file. Texture maps are saved as .png files next to it,
+    /// named after the .mtl file and the map they're used for.
+    pub fn save_mtl(&self, path: &Path) -> Result<(), std::io::Error> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let illum = match self.illumination_model {
+            IlluminationModel::Constant => 0,
+            IlluminationModel::Lambert => 1,
+            IlluminationModel::BlinnPhong => 2,
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "newmtl {}", self.mtl_name())?;
+        writeln!(writer, "Ka {} {} {}", self.ambient.x, self.ambient.y, self.ambient.z)?;
+        writeln!(writer, "Kd {} {} {}", self.diffuse.x, self.diffuse.y, self.diffuse.z)?;
+        writeln!(writer, "Ks {} {} {}", self.specular.x, self.specular.y, self.specular.z)?;
+        writeln!(writer, "Ns {}", self.shininess)?;
+        writeln!(writer, "d {}", self.dissolve)?;
+        writeln!(writer, "illum {}", illum)?;
+
+        let maps = [
+            ("map_Ka", &self.ambient_texture),
+            ("map_Kd", &self.diffuse_texture),
+            ("map_Ks", &self.specular_texture),
+            ("map_Bump", &self.normal_texture),
+            ("map_Ns", &self.shininess_texture),
+            ("map_d", &self.dissolve_texture),
+        ];
+        for (keyword, texture) in maps {
+            let Some(texture) = texture else { continue };
+            let file_name = format!("{}_{}.png", stem, keyword);
+            texture
+                .image
+                .save(dir.join(&file_name))
+                .map_err(std::io::Error::other)?;
+            writeln!(writer, "{} {}", keyword, file_name)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Shades a surface point, returning its RGBA colour.
+    ///
+    /// `view_direction` is a unit vector pointing from the surface towards the viewer,
+    /// and `ambient_light` is the light reaching the surface from all directions.
+    pub fn shade<I>(
+        &self,
+        attributes: &VertexAttributes,
+        view_direction: Vec3,
+        ambient_light: Vec3,
+        lights: I,
+    ) -> Vec4
+    where
+        I: IntoIterator<Item = IncidentLight>,
+    {
+        let sample = |texture: &Option<Texture>| {
+            texture
+                .as_ref()
+                .map_or(Vec4::one(), |t| t.sample(attributes.uv))
+        };
+
+        let diffuse_sample = sample(&self.diffuse_texture);
+        let diffuse = self.diffuse * attributes.color * diffuse_sample.xyz();
+        let alpha = self.dissolve * diffuse_sample.w * sample(&self.dissolve_texture).x;
+
+        if self.illumination_model == IlluminationModel::Constant {
+            return Vec4::new(diffuse.x, diffuse.y, diffuse.z, alpha);
+        }
+
+        let ambient = self.ambient * sample(&self.ambient_texture).xyz();
+        let specular = self.specular * sample(&self.specular_texture).xyz();
+        let shininess = self.shininess * sample(&self.shininess_texture).x;
+        let normal = attributes.normal.normalized();
+
+        let mut color = ambient * ambient_light;
+        for light in lights {
+            let n_dot_l = normal.dot(light.direction);
+            if n_dot_l <= 0. {
+                continue;
+            }
+            color += diffuse * light.radiance * n_dot_l;
+
+            if self.illumination_model == IlluminationModel::BlinnPhong {
+                // ? https://en.wikipedia.org/wiki/Blinn%E2%80%93Phong_reflection_model
+                let halfway = (light.direction + view_direction).normalized();
+                let n_dot_h = normal.dot(halfway).max(0.);
+                color += specular * light.radiance * n_dot_h.powf(shininess);
+            }
+        }
+
+        Vec4::new(color.x, color.y, color.z, alpha)
+    }
+}
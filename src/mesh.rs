@@ -0,0 +1,239 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
+
+use crate::{object::Object, triangle::Triangle3D};
+use ultraviolet::Vec3;
+
+/// The crease angle used for meshes that are loaded without normals, in radians.
+pub const DEFAULT_CREASE_ANGLE: f32 = PI / 6.;
+
+/// How close vertices of meshes loaded without shared vertices have to be to get welded together.
+pub const DEFAULT_WELD_EPSILON: f32 = 1e-5;
+
+impl Object {
+    /// Merges vertices that are within `epsilon` of each other, along with their normals,
+    /// UV coordinates and colours if the object has them per vertex. Vertices on UV seams are
+    /// kept apart, as are those with different normals. Triangles that collapse are removed.
+    pub fn weld_vertices(&mut self, epsilon: f32) {
+        let vertex_count = self.vertices.len();
+        let has_normals = self.normals.len() == vertex_count;
+        let has_uvs = self.uv_coords.len() == vertex_count;
+        let has_colors = self.colors.len() == vertex_count;
+
+        let can_weld = |a: usize, b: usize| {
+            (self.vertices[a] - self.vertices[b]).mag() <= epsilon
+                && (!has_normals || (self.normals[a] - self.normals[b]).mag() <= epsilon)
+                && (!has_uvs || (self.uv_coords[a] - self.uv_coords[b]).mag() <= epsilon)
+                && (!has_colors || (self.colors[a] - self.colors[b]).mag() <= epsilon)
+        };
+
+        // * Vertices are bucketed into a grid of `epsilon` sized cells, so only the neighbouring
+        // * cells have to be searched for each vertex.
+        let cell_size = epsilon.max(1e-6);
+        let cell = |v: Vec3| (v / cell_size).map(f32::floor);
+        let mut grid = HashMap::<[i64; 3], Vec<usize>>::new();
+        let mut kept = Vec::new();
+        let mut remap = Vec::with_capacity(vertex_count);
+
+        for i in 0..vertex_count {
+            let c = cell(self.vertices[i]);
+            let mut found = None;
+            'search: for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let key = [c.x as i64 + x, c.y as i64 + y, c.z as i64 + z];
+                        let candidates = grid.get(&key).map_or(&[][..], Vec::as_slice);
+                        found = candidates.iter().copied().find(|j| can_weld(kept[*j], i));
+                        if found.is_some() {
+                            break 'search;
+                        }
+                    }
+                }
+            }
+
+            remap.push(found.unwrap_or_else(|| {
+                kept.push(i);
+                let key = [c.x as i64, c.y as i64, c.z as i64];
+                grid.entry(key).or_default().push(kept.len() - 1);
+                kept.len() - 1
+            }));
+        }
+
+        let mut triangles = Vec::with_capacity(self.triangles.len());
+        for indices in &self.triangles {
+            let [a, b, c] = indices.map(|i| remap[i]);
+            if a != b && b != c && c != a {
+                triangles.push([a, b, c]);
+            }
+        }
+
+        self.vertices = kept.iter().map(|i| self.vertices[*i]).collect();
+        self.invalidate_bounds();
+        if has_normals {
+            self.normals = kept.iter().map(|i| self.normals[*i]).collect();
+        }
+        if has_uvs {
+            self.uv_coords = kept.iter().map(|i| self.uv_coords[*i]).collect();
+        }
+        if has_colors {
+            self.colors = kept.iter().map(|i| self.colors[*i]).collect();
+        }
+        self.triangles = triangles;
+    }
+
+    /// Replaces the object's normals with smooth per-vertex normals, where each face's
+    /// contribution is weighted by its angle at the vertex.
+    ///
+    /// Faces only get smoothed together if the angle between their normals is at most
+    /// `crease_angle` (in radians). Vertices on sharper edges are duplicated, so that each
+    /// side of the edge gets its own normal. Vertices at the same position are smoothed
+    /// together even if they aren't shared, such as those on UV seams.
+    // ? http://www.bytehazard.com/articles/vertnorm.html
+    pub fn compute_smooth_normals(&mut self, crease_angle: f32) {
+        self.compute_smooth_normals_with_sharp_edges(crease_angle, &[]);
+    }
+
+    /// Like [`Object::compute_smooth_normals`], but faces are never smoothed together across
+    /// `sharp_edges`, however small the angle between them. Edges are given as pairs of vertex
+    /// indices, as in [`Object::subdivided`].
+    pub(crate) fn compute_smooth_normals_with_sharp_edges(&mut self, crease_angle: f32, sharp_edges: &[[usize; 2]]) {
+        let cos_crease = crease_angle.cos();
+        let canonical = self.canonical_vertices();
+        let sharp = sharp_edges
+            .iter()
+            .filter(|[a, b]| *a < canonical.len() && *b < canonical.len())
+            .map(|[a, b]| [canonical[*a].min(canonical[*b]), canonical[*a].max(canonical[*b])])
+            .collect::<HashSet<_>>();
+
+        // * Unit normal of each face, along with its angle at each of its corners.
+        let faces = self
+            .triangles
+            .iter()
+            .map(|indices| {
+                let [a, b, c] = indices.map(|i| self.vertices[i]);
+                let normal = Triangle3D::new(a, b, c).unit_normal();
+                let angle = |p: Vec3, q: Vec3, r: Vec3| {
+                    let (u, v) = (q - p, r - p);
+                    if u.mag_sq() > 0. && v.mag_sq() > 0. {
+                        u.normalized().dot(v.normalized()).clamp(-1., 1.).acos()
+                    } else {
+                        0.
+                    }
+                };
+                (normal, [angle(a, b, c), angle(b, c, a), angle(c, a, b)])
+            })
+            .collect::<Vec<_>>();
+
+        // * Every corner of every face, grouped by the exact position of its vertex.
+        let position_key = |v: Vec3| [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+        let mut corners_at = HashMap::<[u32; 3], Vec<(usize, usize)>>::new();
+        for (tri_index, indices) in self.triangles.iter().enumerate() {
+            for (corner, i) in indices.iter().enumerate() {
+                corners_at
+                    .entry(position_key(self.vertices[*i]))
+                    .or_default()
+                    .push((tri_index, corner));
+            }
+        }
+
+        // * The faces around each position are split into fans by the sharp edges leading away
+        // * from it, and only faces in the same fan are smoothed together.
+        let mut fans = vec![[0; 3]; self.triangles.len()];
+        if !sharp.is_empty() {
+            for corners in corners_at.values() {
+                let edges = corners
+                    .iter()
+                    .map(|(tri_index, corner)| {
+                        let indices = self.triangles[*tri_index].map(|i| canonical[i]);
+                        (indices[*corner], [indices[(corner + 1) % 3], indices[(corner + 2) % 3]])
+                    })
+                    .collect::<Vec<_>>();
+                let mut parent = (0..corners.len()).collect::<Vec<_>>();
+                let root = |parent: &[usize], mut i: usize| {
+                    while parent[i] != i {
+                        i = parent[i];
+                    }
+                    i
+                };
+                for i in 0..corners.len() {
+                    for j in i + 1..corners.len() {
+                        let (center, ends) = edges[i];
+                        let is_sharp = |end: usize| sharp.contains(&[center.min(end), center.max(end)]);
+                        let joined = ends.iter().any(|end| edges[j].1.contains(end) && !is_sharp(*end));
+                        if joined {
+                            let (a, b) = (root(&parent, i), root(&parent, j));
+                            parent[a] = b;
+                        }
+                    }
+                }
+                for (i, (tri_index, corner)) in corners.iter().enumerate() {
+                    fans[*tri_index][*corner] = root(&parent, i);
+                }
+            }
+        }
+
+        let mut normals = vec![Vec3::zero(); self.vertices.len()];
+        // * The normals already given to each vertex, and the vertex to use for each of them.
+        let mut assigned = vec![Vec::<(Vec3, usize)>::new(); self.vertices.len()];
+        let has_uvs = self.uv_coords.len() == self.vertices.len();
+        let has_colors = self.colors.len() == self.vertices.len();
+
+        for tri_index in 0..self.triangles.len() {
+            for corner in 0..3 {
+                let vertex = self.triangles[tri_index][corner];
+                let face_normal = faces[tri_index].0;
+
+                let mut normal = Vec3::zero();
+                for (other, other_corner) in &corners_at[&position_key(self.vertices[vertex])] {
+                    let (other_normal, angles) = faces[*other];
+                    let same_fan = fans[*other][*other_corner] == fans[tri_index][corner];
+                    if *other == tri_index || (same_fan && face_normal.dot(other_normal) >= cos_crease) {
+                        normal += other_normal * angles[*other_corner];
+                    }
+                }
+                let normal = if normal.mag_sq() > 0. { normal.normalized() } else { face_normal };
+
+                // * Corners that share a vertex but end up with different normals split it.
+                let existing = assigned[vertex].iter().find(|(n, _)| *n == normal).map(|(_, v)| *v);
+                let new_vertex = existing.unwrap_or_else(|| {
+                    let new_vertex = if assigned[vertex].is_empty() {
+                        vertex
+                    } else {
+                        self.vertices.push(self.vertices[vertex]);
+                        if has_uvs {
+                            self.uv_coords.push(self.uv_coords[vertex]);
+                        }
+                        if has_colors {
+                            self.colors.push(self.colors[vertex]);
+                        }
+                        normals.push(Vec3::zero());
+                        self.vertices.len() - 1
+                    };
+                    normals[new_vertex] = normal;
+                    assigned[vertex].push((normal, new_vertex));
+                    new_vertex
+                });
+                self.triangles[tri_index][corner] = new_vertex;
+            }
+        }
+
+        self.normals = normals;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::tests::{cube, unshared};
+
+    #[test]
+    fn welds_unshared_cube() {
+        let mut obj = unshared(&cube(), 1e-6);
+        assert_eq!(obj.vertices.len(), 36);
+
+        obj.weld_vertices(1e-5);
+        assert_eq!(obj.vertices.len(), 8);
+        assert_eq!(obj.triangles.len(), 12);
+    }
+}
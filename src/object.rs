@@ -1,62 +1,185 @@
-use crate::triangle::Triangle3D;
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+    sync::OnceLock,
+};
+
+use crate::{
+    aabb::AABB3,
+    error::{AssetError, AssetFormat},
+    material::Material,
+    mesh::{DEFAULT_CREASE_ANGLE, DEFAULT_WELD_EPSILON},
+    ply::read_ply,
+    triangle::{Triangle3D, Winding},
+    vertex::VertexAttributes,
+};
+use image::RgbaImage;
 use stl_io::Vector;
-use ultraviolet::{Isometry3, Rotor3, Vec2, Vec3};
+use ultraviolet::{Bivec3, Mat3, Mat4, Rotor3, Vec2, Vec3};
+
+/// The encoding of a written .stl file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StlFormat {
+    #[default]
+    Binary,
+    Ascii,
+}
 
+#[derive(Clone)]
 pub struct Object {
+    /// Used to find the object in a [`SceneGraph`](crate::scene_graph::SceneGraph).
+    pub name: String,
     pub position: Vec3,
     pub rotation: Rotor3,
+    /// Scale along each of the object's local axes. Negative values mirror the object.
+    pub scale: Vec3,
 
+    /// Call [`Object::invalidate_bounds`] after moving these, as [`Object::local_bounds`] is cached.
     pub vertices: Vec<Vec3>,
     pub triangles: Vec<[usize; 3]>,
 
-    pub normals: Vec<Vec3>, // * indexed per vertice, not per face
+    /// One normal per vertex. Triangles are shaded with their face normals instead if the object
+    /// doesn't have a normal for each vertex.
+    pub normals: Vec<Vec3>,
     pub uv_coords: Vec<Vec2>,
+    /// Per-vertex RGB colours, which multiply the material's diffuse colour.
+    /// Empty if the object doesn't have vertex colours.
+    pub colors: Vec<Vec3>,
+
+    pub material: Material,
 
-    pub textures: Vec<image::DynamicImage>,
+    /// Whether triangles facing away from the camera are skipped when rendering.
+    pub cull_back_faces: bool,
+    /// The winding order of the object's triangles when seen from the front.
+    pub front_face: Winding,
+
+    /// Simplified versions of the object, from most to least detailed, which are rendered
+    /// instead of it when it's small on screen. See [`Object::generate_lods`].
+    pub lods: Vec<Object>,
+
+    /// The bounding box of [`Object::vertices`], computed the first time it's needed.
+    pub(crate) bounds: OnceLock<Option<AABB3>>,
 }
 
 impl Object {
-    pub fn load_from_stl(path: &str) -> Result<Self, std::io::Error> {
+    /// Creates an object at the origin without any geometry.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            position: Vec3::zero(),
+            rotation: Rotor3::identity(),
+            scale: Vec3::one(),
+            vertices: Vec::new(),
+            triangles: Vec::new(),
+            normals: Vec::new(),
+            uv_coords: Vec::new(),
+            colors: Vec::new(),
+            material: Material::default(),
+            cull_back_faces: false,
+            front_face: Winding::default(),
+            lods: Vec::new(),
+            bounds: OnceLock::new(),
+        }
+    }
+
+    /// Loads an ASCII or binary .stl file. Vertices that are almost at the same position are
+    /// welded together, see [`Object::weld_vertices`], and the face normals stored in the file
+    /// are replaced by smooth vertex normals, see [`Object::compute_smooth_normals`].
+    pub fn load_from_stl(path: &str) -> Result<Self, AssetError> {
         fn stl_vector_to_vec3(vector: Vector<f32>) -> Vec3 {
             Vec3::new(vector[0], vector[1], vector[2])
         }
 
-        let mut file = std::fs::OpenOptions::new().read(true).open(path)?;
-        let mesh = stl_io::read_stl(&mut file)?;
+        let error = |e| AssetError::from_io(path, AssetFormat::Stl, e);
+        let mut file = std::fs::OpenOptions::new().read(true).open(path).map_err(error)?;
+        let mesh = stl_io::read_stl(&mut file).map_err(error)?;
         
         let vertices = mesh
             .vertices
             .into_iter()
-            .map(|v| stl_vector_to_vec3(v))
+            .map(stl_vector_to_vec3)
             .collect();
 
-        let (triangles, normals) = mesh
+        let triangles = mesh
             .faces
             .into_iter()
-            .map(|f| (f.vertices, stl_vector_to_vec3(f.normal)))
-            .unzip();
+            .map(|f| f.vertices)
+            .collect();
 
-        Ok(
-            Self {
-                position: Vec3::zero(),
-                rotation: Rotor3::identity(),
-                vertices,
-                triangles,
-                normals,
-                uv_coords: Vec::new(),
-                textures: Vec::new(),
-            }
-        )
+        let mut obj = Self {
+            vertices,
+            triangles,
+            ..Self::new(name_from_path(path))
+        };
+        obj.weld_vertices(DEFAULT_WELD_EPSILON);
+        obj.compute_smooth_normals(DEFAULT_CREASE_ANGLE);
+        Ok(obj)
     }
 
-    pub fn load_many_from_obj(path: &str) -> Result<Vec<Self>, tobj::LoadError> {
-        let mut load_options = tobj::LoadOptions::default();
-            load_options.triangulate = true;
-            load_options.ignore_lines = true;
-            load_options.ignore_points = true;
+    /// Loads an ASCII or binary .ply file, including any per-vertex normals, UV coordinates
+    /// and colours.
+    pub fn load_from_ply(path: &str) -> Result<Self, AssetError> {
+        let error = |e| AssetError::from_io(path, AssetFormat::Ply, e);
+        let mut file = std::fs::OpenOptions::new().read(true).open(path).map_err(error)?;
+        let mesh = read_ply(&mut file).map_err(error)?;
 
-        let (models, materials) = tobj::load_obj(path, &load_options)?;
-        let materials = materials?;
+        let mut obj = Self {
+            vertices: mesh.vertices,
+            triangles: mesh.triangles,
+            normals: mesh.normals,
+            uv_coords: mesh.uv_coords,
+            colors: mesh.colors,
+            ..Self::new(name_from_path(path))
+        };
+        obj.remove_out_of_range_triangles();
+        Ok(obj)
+    }
+
+    /// Loads every model of an .obj file, along with the materials and textures they use. Vertex
+    /// colours are read from the end of `v` statements if the file has them.
+    pub fn load_many_from_obj(path: &str) -> Result<Vec<Self>, AssetError> {
+        let load_options = tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ignore_lines: true,
+            ignore_points: true,
+        };
+
+        let file = File::open(path).map_err(|e| AssetError::from_io(path, AssetFormat::Obj, e))?;
+        let obj_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        // * tobj doesn't say which .mtl file failed or why, so they're opened here instead,
+        // * keeping the first error.
+        let mtl_error = RefCell::new(None);
+        let load_mtl = |mtl_path: &Path| {
+            let mtl_path = obj_dir.join(mtl_path);
+            let file = File::open(&mtl_path).map_err(|e| AssetError::from_io(&mtl_path, AssetFormat::Mtl, e));
+            let materials = file.and_then(|file| {
+                tobj::load_mtl_buf(&mut BufReader::new(file)).map_err(|e| AssetError::Invalid {
+                    path: mtl_path.clone(),
+                    format: AssetFormat::Mtl,
+                    source: Box::new(e),
+                })
+            });
+            materials.map_err(|e| {
+                mtl_error.borrow_mut().get_or_insert(e);
+                tobj::LoadError::OpenFileFailed
+            })
+        };
+
+        let (models, materials) = tobj::load_obj_buf(&mut BufReader::new(file), &load_options, load_mtl)
+            .map_err(|e| AssetError::Invalid {
+                path: path.into(),
+                format: AssetFormat::Obj,
+                source: Box::new(e),
+            })?;
+        if let Some(e) = mtl_error.into_inner() {
+            return Err(e);
+        }
+        // * Any other errors were recorded above.
+        let materials = materials.unwrap_or_default();
 
         let mut res = Vec::new();
         for model in models {
@@ -70,55 +193,222 @@ impl Object {
                 .map(|c| [c[0] as usize, c[1] as usize, c[2] as usize])
                 .collect();
 
-            let normals_unsorted = model.mesh.normals
+            // * `single_index` makes tobj duplicate vertices where needed, so that normals
+            // * and UV coordinates share the position indices.
+            let normals = model.mesh.normals
                 .chunks_exact(3)
                 .map(|c| Vec3::new(c[0], c[1], c[2]))
-                .collect::<Vec<_>>();
-            let normals = model.mesh.normal_indices
-                .into_iter()
-                .map(|i| normals_unsorted[i as usize])
                 .collect();
 
-            let uv_coords_unsorted = model.mesh.texcoords
+            let uv_coords = model.mesh.texcoords
                 .chunks_exact(2)
                 .map(|c| Vec2::new(c[0], c[1]))
-                .collect::<Vec<_>>();
-            let uv_coords = model.mesh.texcoord_indices
-                .into_iter()
-                .map(|i| uv_coords_unsorted[i as usize])
                 .collect();
 
-            // TODO: Implement .mtl materials, which use Lambertian shading:
-            // ? https://paulbourke.net/dataformats/mtl/
-            // ? https://www.scratchapixel.com/lessons/3d-basic-rendering/introduction-to-shading/diffuse-lambertian-shading.html
-            
-            let mut textures = Vec::new();
-            if let Some(mat_idx) = model.mesh.material_id {
-                if let Some(material) = materials.get(mat_idx) {
-                    if let Some(texture_path) = &material.diffuse_texture {
-                        if let Ok(reader) = image::io::Reader::open(texture_path) {
-                            if let Ok(texture) = reader.decode() {
-                                textures.push(texture);
-                            }
-                        }
+            let colors = model.mesh.vertex_color
+                .chunks_exact(3)
+                .map(|c| Vec3::new(c[0], c[1], c[2]))
+                .collect();
+
+            let material = model.mesh.material_id
+                .and_then(|mat_idx| materials.get(mat_idx))
+                .map_or_else(|| Ok(Material::default()), |material| Material::from_mtl(material, obj_dir))?;
+
+            let mut obj = Self {
+                vertices,
+                triangles,
+                normals,
+                uv_coords,
+                colors,
+                material,
+                ..Self::new(model.name)
+            };
+            obj.remove_out_of_range_triangles();
+            res.push(obj);
+        }
+
+        Ok(res)
+    }
+
+    /// Converts a primitive of a glTF mesh, using the buffers loaded by [`gltf::import_buffers`] and
+    /// the images loaded by [`Texture::load_gltf_image`](crate::texture::Texture::load_gltf_image).
+    /// Returns `None` if the primitive is made of points or lines, or doesn't have any positions.
+    pub(crate) fn from_gltf_primitive(
+        name: &str,
+        primitive: &gltf::Primitive,
+        buffers: &[gltf::buffer::Data],
+        images: &[RgbaImage],
+    ) -> Option<Self> {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+        let vertices = reader.read_positions()?.map(Vec3::from).collect::<Vec<_>>();
+
+        let normals = reader
+            .read_normals()
+            .map_or_else(Vec::new, |normals| normals.map(Vec3::from).collect());
+
+        // * glTF texture coordinates point down from the top of the image, unlike OBJ's.
+        let material = primitive.material();
+        let uv_set = material
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map_or(0, |info| info.tex_coord());
+        let uv_coords = reader.read_tex_coords(uv_set).map_or_else(Vec::new, |uvs| {
+            uvs.into_f32().map(|[u, v]| Vec2::new(u, 1. - v)).collect()
+        });
+
+        let colors = reader.read_colors(0).map_or_else(Vec::new, |colors| {
+            colors.into_rgb_f32().map(Vec3::from).collect()
+        });
+
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..vertices.len()).collect::<Vec<_>>(),
+        };
+        let triangles = match primitive.mode() {
+            gltf::mesh::Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect(),
+            // * Every other triangle of a strip is flipped, to keep the winding order consistent.
+            gltf::mesh::Mode::TriangleStrip => indices
+                .windows(3)
+                .enumerate()
+                .map(|(i, w)| if i % 2 == 0 { [w[0], w[1], w[2]] } else { [w[1], w[0], w[2]] })
+                .collect(),
+            gltf::mesh::Mode::TriangleFan => indices
+                .windows(2)
+                .skip(1)
+                .map(|w| [indices[0], w[0], w[1]])
+                .collect(),
+            _ => return None,
+        };
+
+        let mut obj = Self {
+            vertices,
+            triangles,
+            normals,
+            uv_coords,
+            colors,
+            material: Material::from_gltf(&material, images),
+            cull_back_faces: !material.double_sided(),
+            ..Self::new(name)
+        };
+        obj.remove_out_of_range_triangles();
+        Some(obj)
+    }
+
+    /// Writes the object's triangles to an .stl file, with one normal per face. If
+    /// `bake_transform` is set, the object is written as transformed by [`Object::get_transform`].
+    pub fn save_stl(&self, path: &str, format: StlFormat, bake_transform: bool) -> Result<(), std::io::Error> {
+        let (vertices, _, triangles) = self.export_geometry(bake_transform);
+        let faces = triangles
+            .iter()
+            .map(|indices| {
+                let positions = indices.map(|i| vertices[i]);
+                let normal = Triangle3D::new(positions[0], positions[1], positions[2]).unit_normal();
+                (normal, positions)
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            StlFormat::Binary => {
+                let faces = faces.iter().map(|(normal, positions)| stl_io::Triangle {
+                    normal: Vector::new((*normal).into()),
+                    vertices: positions.map(|v| Vector::new(v.into())),
+                });
+                stl_io::write_stl(&mut writer, faces)?;
+            }
+            StlFormat::Ascii => {
+                // ? https://paulbourke.net/dataformats/stl/
+                writeln!(writer, "solid {}", self.name)?;
+                for (normal, positions) in &faces {
+                    writeln!(writer, "facet normal {} {} {}", normal.x, normal.y, normal.z)?;
+                    writeln!(writer, "  outer loop")?;
+                    for v in positions {
+                        writeln!(writer, "    vertex {} {} {}", v.x, v.y, v.z)?;
                     }
+                    writeln!(writer, "  endloop")?;
+                    writeln!(writer, "endfacet")?;
                 }
+                writeln!(writer, "endsolid {}", self.name)?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Writes the object to an .obj file, and its material to an .mtl file with the same name
+    /// (see [`Material::save_mtl`]). Vertex colours are appended to the `v` statements. If
+    /// `bake_transform` is set, the object is written as transformed by [`Object::get_transform`].
+    pub fn save_obj(&self, path: &str, bake_transform: bool) -> Result<(), std::io::Error> {
+        let path = Path::new(path);
+        let mtl_path = path.with_extension("mtl");
+        self.material.save_mtl(&mtl_path)?;
+
+        let (vertices, normals, triangles) = self.export_geometry(bake_transform);
+        let has_uvs = self.uv_coords.len() == vertices.len();
+        let has_colors = self.colors.len() == vertices.len();
+        let has_normals = normals.len() == vertices.len();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "mtllib {}", mtl_path.file_name().unwrap_or_default().to_string_lossy())?;
+        writeln!(writer, "o {}", if self.name.is_empty() { "object" } else { &self.name })?;
+
+        for (i, v) in vertices.iter().enumerate() {
+            if has_colors {
+                let c = self.colors[i];
+                writeln!(writer, "v {} {} {} {} {} {}", v.x, v.y, v.z, c.x, c.y, c.z)?;
+            } else {
+                writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+            }
+        }
+        if has_uvs {
+            for uv in &self.uv_coords {
+                writeln!(writer, "vt {} {}", uv.x, uv.y)?;
             }
+        }
+        if has_normals {
+            for n in &normals {
+                writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+            }
+        }
 
-            res.push(
-                Self {
-                    position: Vec3::zero(),
-                    rotation: Rotor3::identity(),
-                    vertices,
-                    triangles,
-                    normals,
-                    uv_coords,
-                    textures,
+        // * OBJ indices start at 1.
+        writeln!(writer, "usemtl {}", self.material.mtl_name())?;
+        for indices in &triangles {
+            write!(writer, "f")?;
+            for i in indices {
+                match (has_uvs, has_normals) {
+                    (true, true) => write!(writer, " {}/{}/{}", i + 1, i + 1, i + 1)?,
+                    (true, false) => write!(writer, " {}/{}", i + 1, i + 1)?,
+                    (false, true) => write!(writer, " {}//{}", i + 1, i + 1)?,
+                    (false, false) => write!(writer, " {}", i + 1)?,
                 }
-            );
+            }
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    /// Returns the object's vertices, normals and triangles to be written to a file,
+    /// transformed by [`Object::get_transform`] if `bake_transform` is set.
+    fn export_geometry(&self, bake_transform: bool) -> (Vec<Vec3>, Vec<Vec3>, Vec<[usize; 3]>) {
+        if !bake_transform {
+            return (self.vertices.clone(), self.normals.clone(), self.triangles.clone());
         }
 
-        return Ok(res);
+        let transform = self.get_transform();
+        let normal_transform = normal_matrix(transform);
+        let vertices = self.vertices.iter().map(|v| transform.transform_point3(*v)).collect();
+        let normals = self.normals.iter().map(|n| (normal_transform * *n).normalized()).collect();
+        // * Mirroring flips the winding order, so it's flipped back to keep faces pointing outwards.
+        let triangles = if transform.truncate().determinant() < 0. {
+            self.triangles.iter().map(|[a, b, c]| [*a, *c, *b]).collect()
+        } else {
+            self.triangles.clone()
+        };
+        (vertices, normals, triangles)
     }
 
     pub fn get_triangle(&self, indices: [usize; 3]) -> Option<Triangle3D> {
@@ -129,6 +419,8 @@ impl Object {
         ))
     }
 
+    /// Like [`Object::get_triangle`], but panics if any of `indices` is out of range. The loaders
+    /// remove such triangles, see [`Object::remove_out_of_range_triangles`].
     pub fn get_triangle_unchecked(&self, indices: [usize; 3]) -> Triangle3D {
         Triangle3D::new(
             self.vertices[indices[0]],
@@ -137,7 +429,276 @@ impl Object {
         )
     }
 
-    pub fn get_transform(&self) -> Isometry3 {
-        Isometry3::new(self.position, self.rotation)
+    /// Returns the attributes of the 3 vertices of the triangle at `triangle_index`. Normals
+    /// fall back to the triangle's face normal if the object doesn't have one for each vertex.
+    pub fn get_vertex_attributes(&self, triangle_index: usize) -> [VertexAttributes; 3] {
+        let indices = self.triangles[triangle_index];
+
+        let normals = if self.normals.len() == self.vertices.len() {
+            indices.map(|i| self.normals[i])
+        } else {
+            // * Degenerate triangles have no direction to face, so they're left with a zero normal.
+            [self.get_triangle_unchecked(indices).unit_normal(); 3]
+        };
+
+        [0, 1, 2].map(|i| VertexAttributes {
+            position: self.vertices[indices[i]],
+            normal: normals[i],
+            uv: self.uv_coords.get(indices[i]).copied().unwrap_or_default(),
+            color: self.colors.get(indices[i]).copied().unwrap_or(Vec3::one()),
+        })
+    }
+
+    /// Returns the bounding box of the object's vertices in its local space. It's only computed
+    /// once, see [`Object::invalidate_bounds`].
+    pub fn local_bounds(&self) -> Option<AABB3> {
+        *self.bounds.get_or_init(|| AABB3::from_points(self.vertices.iter().copied()))
+    }
+
+    /// Forgets the cached bounding box, so that [`Object::local_bounds`] is computed again from
+    /// [`Object::vertices`] after they've been changed.
+    pub fn invalidate_bounds(&mut self) {
+        self.bounds.take();
+    }
+
+    /// Returns the object's model matrix, which scales, then rotates, then translates it.
+    pub fn get_transform(&self) -> Mat4 {
+        Mat4::from_translation(self.position)
+            * self.rotation.into_matrix().into_homogeneous()
+            * Mat4::from_nonuniform_scale(self.scale)
+    }
+
+    /// Sets the object's position, rotation and scale from a model matrix.
+    /// Any shear in `transform` is lost, since it can't be represented by the object.
+    pub fn set_transform(&mut self, transform: Mat4) {
+        let linear = transform.truncate();
+        let mut scale = Vec3::new(linear.cols[0].mag(), linear.cols[1].mag(), linear.cols[2].mag());
+        if linear.determinant() < 0. {
+            scale.x = -scale.x;
+        }
+        let rotation = Mat3::new(
+            linear.cols[0] / scale.x,
+            linear.cols[1] / scale.y,
+            linear.cols[2] / scale.z,
+        );
+
+        self.position = transform.extract_translation();
+        self.rotation = rotation_to_rotor(rotation);
+        self.scale = scale;
+    }
+}
+
+/// Returns the file name of `path` without its extension, to use as an object's name.
+fn name_from_path(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+}
+
+/// Converts a rotation matrix to a rotor. Unlike [`Mat3::into_rotor3`], the square root is
+/// only taken of the largest component, so rotations about a single axis don't pick up
+/// errors of around 1e-3 from rounding noise in the others.
+// ? https://www.euclideanspace.com/maths/geometry/rotations/conversions/matrixToQuaternion/
+fn rotation_to_rotor(m: Mat3) -> Rotor3 {
+    // * Indexed by row, then column.
+    let e = |row: usize, col: usize| m.cols[col][row];
+    let trace = e(0, 0) + e(1, 1) + e(2, 2);
+    let (w, x, y, z) = if trace > 0. {
+        let s = (1. + trace).sqrt() * 2.;
+        (0.25 * s, (e(2, 1) - e(1, 2)) / s, (e(0, 2) - e(2, 0)) / s, (e(1, 0) - e(0, 1)) / s)
+    } else if e(0, 0) > e(1, 1) && e(0, 0) > e(2, 2) {
+        let s = (1. + e(0, 0) - e(1, 1) - e(2, 2)).sqrt() * 2.;
+        ((e(2, 1) - e(1, 2)) / s, 0.25 * s, (e(0, 1) + e(1, 0)) / s, (e(0, 2) + e(2, 0)) / s)
+    } else if e(1, 1) > e(2, 2) {
+        let s = (1. + e(1, 1) - e(0, 0) - e(2, 2)).sqrt() * 2.;
+        ((e(0, 2) - e(2, 0)) / s, (e(0, 1) + e(1, 0)) / s, 0.25 * s, (e(1, 2) + e(2, 1)) / s)
+    } else {
+        let s = (1. + e(2, 2) - e(0, 0) - e(1, 1)).sqrt() * 2.;
+        ((e(1, 0) - e(0, 1)) / s, (e(0, 2) + e(2, 0)) / s, (e(1, 2) + e(2, 1)) / s, 0.25 * s)
+    };
+    // * Same signs as `Mat3::into_rotor3`, which turns i, j and k into -yz, xz and -xy.
+    Rotor3::new(w, Bivec3::new(-z, y, -x)).normalized()
+}
+
+/// Returns the matrix that transforms normals by `transform`, which is the inverse-transpose
+/// of its upper-left 3x3 block. Unlike positions, normals have to stay perpendicular to the
+/// surface under non-uniform scaling.
+// ? https://www.scratchapixel.com/lessons/mathematics-physics-for-computer-graphics/geometry/transforming-normals.html
+pub fn normal_matrix(transform: Mat4) -> Mat3 {
+    transform.truncate().inversed().transposed()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// A cube from 0 to 1 on each axis, with 8 shared vertices and outward facing triangles.
+    pub(crate) fn cube() -> Object {
+        Object {
+            vertices: (0..8)
+                .map(|i| Vec3::new((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32))
+                .collect(),
+            triangles: vec![
+                [0, 2, 3], [0, 3, 1],
+                [4, 5, 7], [4, 7, 6],
+                [0, 1, 5], [0, 5, 4],
+                [2, 6, 7], [2, 7, 3],
+                [0, 4, 6], [0, 6, 2],
+                [1, 3, 7], [1, 7, 5],
+            ],
+            ..Object::new("cube")
+        }
+    }
+
+    /// Gives every corner of every triangle its own vertex, each moved by up to `jitter` on
+    /// each axis, as in .stl files written with rounding errors.
+    pub(crate) fn unshared(obj: &Object, jitter: f32) -> Object {
+        let corners = obj.triangles.iter().flatten().enumerate();
+        Object {
+            vertices: corners
+                .map(|(i, v)| obj.vertices[*v] + Vec3::new(1., -1., 0.5) * jitter * ((i % 3) as f32 - 1.))
+                .collect(),
+            triangles: (0..obj.triangles.len()).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect(),
+            ..Object::new(obj.name.clone())
+        }
+    }
+
+    #[test]
+    fn local_bounds_follow_vertices() {
+        let mut obj = cube();
+        assert_eq!(obj.local_bounds().unwrap().max, Vec3::one());
+        obj.vertices[7] = Vec3::broadcast(2.);
+        obj.invalidate_bounds();
+        assert_eq!(obj.local_bounds().unwrap().max, Vec3::broadcast(2.));
+    }
+
+    #[test]
+    fn set_transform_round_trips() {
+        let xz = |angle: f32| Rotor3::from_rotation_xz(angle).into_matrix();
+        let rotations = [
+            Mat3::identity(),
+            xz(0.9),
+            xz(0.3) * xz(0.6) * xz(0.9),
+            Rotor3::from_rotation_xy(PI).into_matrix() * xz(0.5),
+            Rotor3::from_rotation_yz(-PI / 2.).into_matrix(),
+            Rotor3::from_euler_angles(0.3, -1.2, 2.5).into_matrix(),
+        ];
+        for rotation in rotations {
+            // * Dividing out the scale leaves rounding noise where the rotation should be exactly 0 or 1.
+            for scale in [Vec3::one(), Vec3::new(-3., 6., 0.7)] {
+                let transform = Mat4::from_translation(Vec3::new(1., -2., 3.))
+                    * rotation.into_homogeneous()
+                    * Mat4::from_nonuniform_scale(scale);
+                let mut obj = Object::new("transformed");
+                obj.set_transform(transform);
+
+                let error = (obj.get_transform().as_slice().iter().zip(transform.as_slice()))
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0., f32::max);
+                assert!(error < 1e-5, "{:?} with scale {:?} is off by {}", rotation, scale, error);
+            }
+        }
+    }
+
+    #[test]
+    fn stl_cube_is_welded() {
+        let path = std::env::temp_dir().join("dedede_welded_cube.stl");
+        let path = path.to_str().unwrap();
+        unshared(&cube(), 1e-6).save_stl(path, StlFormat::Binary, false).unwrap();
+        let obj = Object::load_from_stl(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // * Smoothing splits each corner again, once for each of the 3 faces meeting at it.
+        let mut positions = obj.vertices.clone();
+        positions.sort_by(|a, b| a.as_array().partial_cmp(b.as_array()).unwrap());
+        positions.dedup();
+        assert_eq!(positions.len(), 8);
+        assert_eq!(obj.triangles.len(), 12);
+        assert_eq!(obj.vertices.len(), 24);
+    }
+
+    /// The cube mirrored along x and moved away from the origin, so that baking its transform
+    /// has to flip the winding of its triangles.
+    fn mirrored_cube() -> Object {
+        Object {
+            position: Vec3::new(1., 2., 3.),
+            scale: Vec3::new(-2., 1., 1.),
+            ..cube()
+        }
+    }
+
+    /// Asserts that every triangle of the convex `obj` faces away from `center`.
+    fn assert_faces_outwards(obj: &Object, center: Vec3) {
+        for indices in &obj.triangles {
+            let tri = obj.get_triangle_unchecked(*indices);
+            let centroid = (tri.v0 + tri.v1 + tri.v2) / 3.;
+            assert!(tri.cross().dot(centroid - center) > 0., "{:?} faces inwards", tri);
+        }
+    }
+
+    #[test]
+    fn ascii_stl_round_trips() {
+        let obj = mirrored_cube();
+        let path = std::env::temp_dir().join("dedede_ascii_cube.stl");
+        let path = path.to_str().unwrap();
+        obj.save_stl(path, StlFormat::Ascii, true).unwrap();
+        let loaded = Object::load_from_stl(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let transform = obj.get_transform();
+        let corners = obj.vertices.iter().map(|v| transform.transform_point3(*v)).collect::<Vec<_>>();
+        assert_eq!(loaded.triangles.len(), 12);
+        assert!(loaded.vertices.iter().all(|v| corners.iter().any(|c| (*c - *v).mag() < 1e-5)));
+        assert_faces_outwards(&loaded, transform.transform_point3(Vec3::broadcast(0.5)));
+    }
+
+    #[test]
+    fn obj_and_mtl_round_trip() {
+        let mut obj = mirrored_cube();
+        obj.uv_coords = obj.vertices.iter().map(|v| Vec2::new(v.x, v.y)).collect();
+        obj.colors = obj.vertices.iter().map(|v| Vec3::new(v.z, 0.5, 1. - v.x)).collect();
+        obj.compute_smooth_normals(DEFAULT_CREASE_ANGLE);
+        obj.material.name = "painted".into();
+        obj.material.diffuse = Vec3::new(0.2, 0.4, 0.6);
+
+        let path = std::env::temp_dir().join("dedede_round_trip.obj");
+        obj.save_obj(path.to_str().unwrap(), true).unwrap();
+        let loaded = Object::load_many_from_obj(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("mtl")).unwrap();
+
+        let [loaded] = loaded.as_slice() else {
+            panic!("expected 1 object, found {}", loaded.len());
+        };
+        assert_eq!(loaded.name, "cube");
+        assert_eq!(loaded.material.name, "painted");
+        assert_eq!(loaded.material.diffuse, Vec3::new(0.2, 0.4, 0.6));
+        assert_eq!(loaded.triangles.len(), 12);
+
+        let transform = obj.get_transform();
+        let center = transform.transform_point3(Vec3::broadcast(0.5));
+        assert_faces_outwards(loaded, center);
+        for (i, v) in loaded.vertices.iter().enumerate() {
+            // * UVs and colours are kept as they were, while positions and normals are transformed.
+            let original = obj
+                .vertices
+                .iter()
+                .position(|o| (transform.transform_point3(*o) - *v).mag() < 1e-5)
+                .unwrap();
+            assert_eq!(loaded.uv_coords[i], obj.uv_coords[original]);
+            assert_eq!(loaded.colors[i], obj.colors[original]);
+            assert!(loaded.normals[i].dot(*v - center) > 0.);
+        }
+    }
+
+    #[test]
+    fn degenerate_triangle_gets_zero_normal() {
+        let obj = Object {
+            vertices: vec![Vec3::zero(), Vec3::unit_x(), Vec3::unit_x() * 2.],
+            triangles: vec![[0, 1, 2]],
+            ..Object::new("line")
+        };
+        assert!(obj.get_vertex_attributes(0).iter().all(|v| v.normal == Vec3::zero()));
     }
 }
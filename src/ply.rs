@@ -0,0 +1,399 @@
+use std::io::{Error, ErrorKind, Read};
+
+use ultraviolet::{Vec2, Vec3};
+
+/// The geometry read from a .ply file, with each attribute left empty if the file doesn't have it.
+// ? https://paulbourke.net/dataformats/ply/
+pub struct PlyMesh {
+    pub vertices: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uv_coords: Vec<Vec2>,
+    /// RGB colours in the range `0..=1`.
+    pub colors: Vec<Vec3>,
+    /// Polygons are triangulated as fans around their first vertex.
+    pub triangles: Vec<[usize; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, Error> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return Err(invalid_data(format!("unknown property type `{}`", name))),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    /// The value that integer colour channels of this type use for full intensity.
+    fn max_color(&self) -> f64 {
+        match self {
+            ScalarType::I8 => i8::MAX as f64,
+            ScalarType::U8 => u8::MAX as f64,
+            ScalarType::I16 => i16::MAX as f64,
+            ScalarType::U16 => u16::MAX as f64,
+            ScalarType::I32 => i32::MAX as f64,
+            ScalarType::U32 => u32::MAX as f64,
+            ScalarType::F32 | ScalarType::F64 => 1.,
+        }
+    }
+}
+
+enum Property {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Property)>,
+}
+
+impl Element {
+    fn property_index(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|(name, _)| names.contains(&name.as_str()))
+    }
+}
+
+/// Reads the values stored after the header, in any of the formats.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Body<'a> {
+    fn new(format: Format, bytes: &'a [u8]) -> Result<Self, Error> {
+        let text = match format {
+            Format::Ascii => std::str::from_utf8(bytes).map_err(|e| invalid_data(e.to_string()))?,
+            _ => "",
+        };
+        Ok(Self {
+            format,
+            bytes,
+            tokens: text.split_ascii_whitespace(),
+        })
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, Error> {
+        if self.format == Format::Ascii {
+            let token = self.tokens.next().ok_or_else(unexpected_eof)?;
+            return token
+                .parse()
+                .map_err(|_| invalid_data(format!("invalid number `{}`", token)));
+        }
+
+        if self.bytes.len() < ty.size() {
+            return Err(unexpected_eof());
+        }
+        let (bytes, rest) = self.bytes.split_at(ty.size());
+        self.bytes = rest;
+
+        macro_rules! from_bytes {
+            ($t:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                (if self.format == Format::BinaryLittleEndian {
+                    <$t>::from_le_bytes(bytes)
+                } else {
+                    <$t>::from_be_bytes(bytes)
+                }) as f64
+            }};
+        }
+        Ok(match ty {
+            ScalarType::I8 => from_bytes!(i8),
+            ScalarType::U8 => from_bytes!(u8),
+            ScalarType::I16 => from_bytes!(i16),
+            ScalarType::U16 => from_bytes!(u16),
+            ScalarType::I32 => from_bytes!(i32),
+            ScalarType::U32 => from_bytes!(u32),
+            ScalarType::F32 => from_bytes!(f32),
+            ScalarType::F64 => from_bytes!(f64),
+        })
+    }
+
+    /// Reads every property of one instance of `element`, with lists flattened into their items.
+    fn read_element(&mut self, element: &Element, values: &mut Vec<Vec<f64>>) -> Result<(), Error> {
+        values.resize(element.properties.len(), Vec::new());
+        for ((_, property), value) in element.properties.iter().zip(values.iter_mut()) {
+            value.clear();
+            match *property {
+                Property::Scalar(ty) => value.push(self.read(ty)?),
+                Property::List { count, item } => {
+                    let count = self.read(count)? as usize;
+                    for _ in 0..count {
+                        value.push(self.read(item)?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn unexpected_eof() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "PLY body ended early")
+}
+
+/// Reads an ASCII, binary little endian or binary big endian .ply file.
+/// Only the `vertex` and `face` elements are used, any others are skipped.
+pub fn read_ply<R: Read>(reader: &mut R) -> Result<PlyMesh, Error> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    // * The header ends at the first line that is exactly `end_header`, and the body starts on
+    // * the line after it. Comments can contain `end_header` too, so it can't just be searched for.
+    let mut line_start = 0;
+    let mut header_end = None;
+    for line in data.split(|b| *b == b'\n') {
+        let next_line_start = (line_start + line.len() + 1).min(data.len());
+        if line.trim_ascii() == b"end_header" {
+            header_end = Some((line_start, next_line_start));
+            break;
+        }
+        line_start = next_line_start;
+    }
+    let (header_end, body_start) =
+        header_end.ok_or_else(|| invalid_data("missing `end_header`".to_owned()))?;
+
+    let header = String::from_utf8_lossy(&data[..header_end]);
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid_data("missing `ply` magic number".to_owned()));
+    }
+
+    let mut format = None;
+    let mut elements = Vec::<Element>::new();
+    for line in lines {
+        let words = line.split_ascii_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid_data(format!("invalid element count `{}`", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_data("property before any element".to_owned()))?
+                .properties
+                .push((name.to_string(), Property::List {
+                    count: ScalarType::parse(count)?,
+                    item: ScalarType::parse(item)?,
+                })),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_data("property before any element".to_owned()))?
+                .properties
+                .push((name.to_string(), Property::Scalar(ScalarType::parse(ty)?))),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid_data(format!("invalid header line `{}`", line))),
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid_data("missing `format`".to_owned()))?;
+    let mut body = Body::new(format, &data[body_start..])?;
+    let mut mesh = PlyMesh {
+        vertices: Vec::new(),
+        normals: Vec::new(),
+        uv_coords: Vec::new(),
+        colors: Vec::new(),
+        triangles: Vec::new(),
+    };
+    let mut values = Vec::new();
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [&["x"], &["y"], &["z"]].map(|n| element.property_index(n));
+                let normal = [&["nx"], &["ny"], &["nz"]].map(|n| element.property_index(n));
+                let uv = [&["u", "s", "texture_u"], &["v", "t", "texture_v"]]
+                    .map(|n| element.property_index(n));
+                let color = [
+                    &["red", "diffuse_red"],
+                    &["green", "diffuse_green"],
+                    &["blue", "diffuse_blue"],
+                ]
+                .map(|n| element.property_index(n));
+                let color_scale = color.map(|i| match i.map(|i| &element.properties[i].1) {
+                    Some(Property::Scalar(ty)) => ty.max_color(),
+                    _ => 1.,
+                });
+
+                let [Some(x), Some(y), Some(z)] = position else {
+                    return Err(invalid_data("vertices are missing a position".to_owned()));
+                };
+                for _ in 0..element.count {
+                    body.read_element(element, &mut values)?;
+                    let get = |i: usize| values[i].first().copied().unwrap_or_default() as f32;
+
+                    mesh.vertices.push(Vec3::new(get(x), get(y), get(z)));
+                    if let [Some(nx), Some(ny), Some(nz)] = normal {
+                        mesh.normals.push(Vec3::new(get(nx), get(ny), get(nz)));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        mesh.uv_coords.push(Vec2::new(get(u), get(v)));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        mesh.colors.push(Vec3::new(
+                            get(r) / color_scale[0] as f32,
+                            get(g) / color_scale[1] as f32,
+                            get(b) / color_scale[2] as f32,
+                        ));
+                    }
+                }
+            }
+            "face" => {
+                let indices = element
+                    .property_index(&["vertex_indices", "vertex_index"])
+                    .ok_or_else(|| invalid_data("faces are missing vertex indices".to_owned()))?;
+                for _ in 0..element.count {
+                    body.read_element(element, &mut values)?;
+                    let polygon = &values[indices];
+                    if let Some(index) = polygon.iter().find(|i| **i < 0. || i.fract() != 0.) {
+                        return Err(invalid_data(format!("invalid vertex index `{}`", index)));
+                    }
+                    for i in 1..polygon.len().saturating_sub(1) {
+                        mesh.triangles.push([polygon[0], polygon[i], polygon[i + 1]].map(|v| v as usize));
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    body.read_element(element, &mut values)?;
+                }
+            }
+        }
+    }
+
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_PROPERTIES: &str = "\
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    const POSITIONS: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+    const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+
+    fn ascii_fixture(comment: &str, face: &str) -> Vec<u8> {
+        let mut text = format!("ply\nformat ascii 1.0\ncomment {}\n{}", comment, HEADER_PROPERTIES);
+        for (p, c) in POSITIONS.iter().zip(COLORS) {
+            text += &format!("{} {} {} {} {} {}\n", p[0], p[1], p[2], c[0], c[1], c[2]);
+        }
+        text += face;
+        text.into_bytes()
+    }
+
+    fn binary_fixture(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut data = format!("ply\nformat {} 1.0\n{}", format, HEADER_PROPERTIES).into_bytes();
+        let f32_bytes = |v: f32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let i32_bytes = |v: i32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        for (p, c) in POSITIONS.iter().zip(COLORS) {
+            data.extend(p.iter().flat_map(|v| f32_bytes(*v)));
+            data.extend(c);
+        }
+        data.push(4);
+        data.extend([0, 1, 2, 3].into_iter().flat_map(i32_bytes));
+        data
+    }
+
+    fn assert_quad(mesh: &PlyMesh) {
+        let positions = POSITIONS.map(|[x, y, z]| Vec3::new(x, y, z));
+        let colors = COLORS.map(|[r, g, b]| Vec3::new(r as f32, g as f32, b as f32) / 255.);
+        assert_eq!(mesh.vertices, positions);
+        assert_eq!(mesh.colors, colors);
+        assert!(mesh.normals.is_empty() && mesh.uv_coords.is_empty());
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn reads_ascii() {
+        assert_quad(&read_ply(&mut ascii_fixture("ascii", "4 0 1 2 3\n").as_slice()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_little_endian() {
+        assert_quad(&read_ply(&mut binary_fixture(false).as_slice()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_big_endian() {
+        assert_quad(&read_ply(&mut binary_fixture(true).as_slice()).unwrap());
+    }
+
+    #[test]
+    fn ignores_end_header_in_comments() {
+        let data = ascii_fixture("end_header isn't the end yet", "4 0 1 2 3\n");
+        assert_quad(&read_ply(&mut data.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_indices() {
+        for face in ["3 0 -1 2\n", "3 0 1.5 2\n"] {
+            let error = read_ply(&mut ascii_fixture("bad index", face).as_slice()).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{:?} wasn't rejected", face);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_body() {
+        let mut data = binary_fixture(false);
+        data.truncate(data.len() - 2);
+        assert_eq!(read_ply(&mut data.as_slice()).err().unwrap().kind(), ErrorKind::UnexpectedEof);
+    }
+}
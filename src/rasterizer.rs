@@ -0,0 +1,583 @@
+use crate::{
+    depth_buffer::{DepthBuffer, DepthCompare},
+    framebuffer::{color_to_rgb, split_rgb, FrameBuffer},
+    shader::Varying,
+};
+use rayon::prelude::*;
+use ultraviolet::{Vec2, Vec3, Vec4};
+
+/// A vertex after the perspective divide, mapped to pixel coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenVertex<V> {
+    pub position: Vec2,
+    pub depth: f32,
+    /// The reciprocal of the vertex's clip space `w`, needed for perspective-correct interpolation.
+    pub inv_w: f32,
+    pub varying: V,
+}
+
+/// The interpolated values of a single pixel covered by a triangle.
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment<V> {
+    /// The center of the pixel, in pixel coordinates.
+    pub position: Vec2,
+    /// Screen-space barycentric weights of the triangle's 3 vertices.
+    pub barycentric: [f32; 3],
+    /// Distance in pixels from [`Fragment::position`] to the edge opposite of each vertex.
+    pub edge_distances: [f32; 3],
+    pub depth: f32,
+    pub varying: V,
+}
+
+/// A rectangular region of the screen, in pixels. The buffers passed to the rasterizer
+/// cover exactly their viewport, so that tiles spanning the full width of the screen can be
+/// rendered into disjoint rows of the same buffers.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Splits the viewport into full-width tiles of at most `tile_height` rows, from top to bottom.
+    pub fn split_into_rows(&self, tile_height: u32) -> Vec<Viewport> {
+        (0..self.height)
+            .step_by(tile_height as usize)
+            .map(|y| Viewport::new(self.x, self.y + y, self.width, tile_height.min(self.height - y)))
+            .collect()
+    }
+}
+
+/// Number of fractional bits in the fixed-point vertex positions used by the rasterizer.
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+
+/// A point in fixed-point subpixel coordinates.
+type FixedPoint = (i64, i64);
+
+fn to_fixed(position: Vec2) -> FixedPoint {
+    (
+        (position.x * SUBPIXEL_ONE as f32).round() as i64,
+        (position.y * SUBPIXEL_ONE as f32).round() as i64,
+    )
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `c`, which is positive if `c` lies to the
+/// right of the edge from `a` to `b` on screen (y pointing down).
+fn orient2d(a: FixedPoint, b: FixedPoint, c: FixedPoint) -> i64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Whether the edge from `a` to `b` of a positively oriented triangle is a top or left edge.
+/// Pixel centers lying exactly on other edges are left to the neighbouring triangle, so that
+/// shared edges are drawn exactly once.
+// ? https://fgiesen.wordpress.com/2013/02/08/triangle-rasterization-in-practice/
+fn is_top_left(a: FixedPoint, b: FixedPoint) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    (dy == 0 && dx > 0) || dy < 0
+}
+
+/// The range of pixels covered by a triangle's bounding box, with exclusive maximums.
+struct PixelBounds {
+    min_x: u32,
+    max_x: u32,
+    min_y: u32,
+    max_y: u32,
+}
+
+impl PixelBounds {
+    fn new<V>(triangle: &[ScreenVertex<V>; 3], viewport: &Viewport) -> Option<Self> {
+        Self::from_fixed(&triangle.each_ref().map(|v| to_fixed(v.position)), viewport)
+    }
+
+    fn from_fixed(vertices: &[FixedPoint; 3], viewport: &Viewport) -> Option<Self> {
+        let min_x = vertices.iter().map(|v| v.0).min()? >> SUBPIXEL_BITS;
+        let max_x = (vertices.iter().map(|v| v.0).max()? + SUBPIXEL_ONE - 1) >> SUBPIXEL_BITS;
+        let min_y = vertices.iter().map(|v| v.1).min()? >> SUBPIXEL_BITS;
+        let max_y = (vertices.iter().map(|v| v.1).max()? + SUBPIXEL_ONE - 1) >> SUBPIXEL_BITS;
+
+        let bounds = Self {
+            min_x: min_x.max(viewport.x as i64) as u32,
+            max_x: max_x.min((viewport.x + viewport.width) as i64).max(0) as u32,
+            min_y: min_y.max(viewport.y as i64) as u32,
+            max_y: max_y.min((viewport.y + viewport.height) as i64).max(0) as u32,
+        };
+        (bounds.min_x < bounds.max_x && bounds.min_y < bounds.max_y).then_some(bounds)
+    }
+}
+
+/// Rasterizes `triangles` in order on the current thread, depth testing them against `depth_buffer`.
+/// `shade` is called with the index of the triangle and each of its fragments, as in [`rasterize_triangle`].
+pub fn rasterize_triangles<V, F>(
+    frame: &mut FrameBuffer,
+    depth_buffer: &mut DepthBuffer,
+    triangles: &[[ScreenVertex<V>; 3]],
+    shade: F,
+) where
+    V: Varying,
+    F: Fn(usize, &Fragment<V>) -> Option<Vec4>,
+{
+    let viewport = Viewport::new(0, 0, frame.width, frame.height);
+
+    for (tri_index, triangle) in triangles.iter().enumerate() {
+        rasterize_triangle(
+            &mut frame.pixels,
+            &mut depth_buffer.values,
+            depth_buffer.compare,
+            &viewport,
+            triangle,
+            |fragment| shade(tri_index, fragment),
+        );
+    }
+}
+
+/// Rasterizes `triangles` in parallel, producing the same image as [`rasterize_triangles`].
+///
+/// The screen is split into tiles of `tile_height` rows spanning its full width, and each triangle
+/// is binned into the tiles its bounding box overlaps. Tiles are then rendered on separate threads
+/// straight into their own rows of `frame` and `depth_buffer`, drawing their triangles in the
+/// original order.
+pub fn rasterize_triangles_tiled<V, F>(
+    frame: &mut FrameBuffer,
+    depth_buffer: &mut DepthBuffer,
+    triangles: &[[ScreenVertex<V>; 3]],
+    tile_height: u32,
+    shade: F,
+) where
+    V: Varying,
+    F: Fn(usize, &Fragment<V>) -> Option<Vec4> + Sync,
+{
+    let screen = Viewport::new(0, 0, frame.width, frame.height);
+    let tiles = screen.split_into_rows(tile_height);
+    if tiles.is_empty() || screen.width == 0 {
+        return;
+    }
+
+    let mut bins = vec![Vec::new(); tiles.len()];
+    for (tri_index, triangle) in triangles.iter().enumerate() {
+        if let Some(bounds) = PixelBounds::new(triangle, &screen) {
+            for row in bounds.min_y / tile_height..=(bounds.max_y - 1) / tile_height {
+                bins[row as usize].push(tri_index);
+            }
+        }
+    }
+
+    let tile_len = (tile_height * frame.width) as usize;
+    let compare = depth_buffer.compare;
+    frame
+        .pixels
+        .par_chunks_mut(tile_len)
+        .zip(depth_buffer.values.par_chunks_mut(tile_len))
+        .zip(tiles.into_par_iter().zip(bins))
+        .for_each(|((pixels, depth_values), (tile, bin))| {
+            for tri_index in bin {
+                rasterize_triangle(pixels, depth_values, compare, &tile, &triangles[tri_index], |fragment| {
+                    shade(tri_index, fragment)
+                });
+            }
+        });
+}
+
+/// Rasterizes a triangle into `pixels`, calling `shade` for every pixel that passes the depth
+/// test against `depth_values`. `shade` returns an RGBA colour, which is blended over the existing
+/// pixel if it isn't opaque, or `None` to leave the pixel and its depth as they were.
+/// `pixels` and `depth_values` cover the pixels of `viewport`, row by row.
+pub fn rasterize_triangle<V, F>(
+    pixels: &mut [u32],
+    depth_values: &mut [f32],
+    compare: DepthCompare,
+    viewport: &Viewport,
+    triangle: &[ScreenVertex<V>; 3],
+    mut shade: F,
+) where
+    V: Varying,
+    F: FnMut(&Fragment<V>) -> Option<Vec4>,
+{
+    // * Each barycentric weight is the distance to the opposite edge divided by the triangle's height over it.
+    let [a, b, c] = triangle.each_ref().map(|v| v.position);
+    let double_area = ((b - a).x * (c - a).y - (b - a).y * (c - a).x).abs();
+    let heights = [(b, c), (c, a), (a, b)].map(|(from, to)| double_area / (to - from).mag());
+
+    rasterize(depth_values, compare, viewport, triangle, |idx, position, barycentric, depth| {
+        let fragment = Fragment {
+            position,
+            barycentric,
+            edge_distances: [0, 1, 2].map(|i| barycentric[i] * heights[i]),
+            depth,
+            varying: interpolate_varying(triangle, barycentric),
+        };
+        let Some(color) = shade(&fragment) else {
+            return false;
+        };
+        pixels[idx] = blend(pixels[idx], color);
+        true
+    });
+}
+
+/// Draws a 1 pixel wide line between the 2 vertices with Bresenham's algorithm, wherever it
+/// passes the depth test. Lines along the edges of triangles have to be moved towards the camera
+/// to be drawn over them, see [`Camera::pull_towards_camera`](crate::camera::Camera::pull_towards_camera).
+// ? https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+pub fn rasterize_line<V>(
+    frame: &mut FrameBuffer,
+    depth_buffer: &mut DepthBuffer,
+    viewport: &Viewport,
+    line: &[ScreenVertex<V>; 2],
+    color: Vec4,
+) {
+    let [start, end] = line.each_ref().map(|v| (v.position.x.floor() as i64, v.position.y.floor() as i64));
+    let (dx, dy) = ((end.0 - start.0).abs(), -(end.1 - start.1).abs());
+    let (step_x, step_y) = ((end.0 - start.0).signum(), (end.1 - start.1).signum());
+    let steps = dx.max(-dy).max(1) as f32;
+
+    let (mut x, mut y) = start;
+    let mut error = dx + dy;
+    for i in 0.. {
+        // * Depth is already divided by w, so it can be interpolated linearly in screen space.
+        let depth = line[0].depth + (line[1].depth - line[0].depth) * (i as f32 / steps).min(1.);
+        plot(frame, depth_buffer, viewport, (x, y), depth, color);
+
+        if (x, y) == end {
+            break;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Draws a square of `size` by `size` pixels centered on the vertex, wherever it passes the
+/// depth test, as in [`rasterize_line`].
+pub fn rasterize_point<V>(
+    frame: &mut FrameBuffer,
+    depth_buffer: &mut DepthBuffer,
+    viewport: &Viewport,
+    point: &ScreenVertex<V>,
+    size: u32,
+    color: Vec4,
+) {
+    let min_x = (point.position.x - size as f32 / 2.).round() as i64;
+    let min_y = (point.position.y - size as f32 / 2.).round() as i64;
+    for y in min_y..min_y + size as i64 {
+        for x in min_x..min_x + size as i64 {
+            plot(frame, depth_buffer, viewport, (x, y), point.depth, color);
+        }
+    }
+}
+
+/// Colours a single pixel if it's inside of `viewport` and passes the depth test.
+fn plot(
+    frame: &mut FrameBuffer,
+    depth_buffer: &mut DepthBuffer,
+    viewport: &Viewport,
+    (x, y): (i64, i64),
+    depth: f32,
+    color: Vec4,
+) {
+    let inside_x = x >= viewport.x as i64 && x < (viewport.x + viewport.width) as i64;
+    let inside_y = y >= viewport.y as i64 && y < (viewport.y + viewport.height) as i64;
+    if !inside_x || !inside_y {
+        return;
+    }
+
+    let idx = ((y - viewport.y as i64) * viewport.width as i64 + x - viewport.x as i64) as usize;
+    if depth_buffer.passes(idx, depth) {
+        depth_buffer.values[idx] = depth;
+        frame.pixels[idx] = blend(frame.pixels[idx], color);
+    }
+}
+
+/// Blends an RGBA colour over a pixel, which is simply replaced if the colour is opaque.
+fn blend(pixel: u32, color: Vec4) -> u32 {
+    if color.w < 1. {
+        let dst = split_rgb(pixel);
+        let dst = Vec3::new(dst[0] as f32, dst[1] as f32, dst[2] as f32) / 255.;
+        color_to_rgb(color.xyz() * color.w + dst * (1. - color.w))
+    } else {
+        color_to_rgb(color.xyz())
+    }
+}
+
+/// Rasterizes a triangle into `depth_buffer` only, as needed for depth pre-passes and shadow maps.
+pub fn rasterize_depth<V>(depth_buffer: &mut DepthBuffer, viewport: &Viewport, triangle: &[ScreenVertex<V>; 3]) {
+    rasterize(&mut depth_buffer.values, depth_buffer.compare, viewport, triangle, |_, _, _, _| true);
+}
+
+/// Walks the pixels covered by a triangle, calling `f` with the pixel's index, center, barycentric
+/// weights and depth for every pixel that passes the `compare` test against `depth_values`. The
+/// pixel's depth is only written if `f` returns `true`.
+///
+/// Coverage is tested at pixel centers using integer edge functions on vertices snapped to
+/// [`SUBPIXEL_BITS`] of subpixel precision, stepped incrementally across the bounding box.
+// ? https://fgiesen.wordpress.com/2013/02/10/optimizing-the-basic-rasterizer/
+fn rasterize<V, F>(
+    depth_values: &mut [f32],
+    compare: DepthCompare,
+    viewport: &Viewport,
+    triangle: &[ScreenVertex<V>; 3],
+    mut f: F,
+) where
+    F: FnMut(usize, Vec2, [f32; 3], f32) -> bool,
+{
+    let mut v = triangle.each_ref().map(|v| to_fixed(v.position));
+    let mut order = [0, 1, 2];
+    let mut area = orient2d(v[0], v[1], v[2]);
+    if area < 0 {
+        v.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+    if area == 0 {
+        return;
+    }
+
+    let Some(bounds) = PixelBounds::from_fixed(&v, viewport) else {
+        return;
+    };
+
+    // * Each weight is the edge function of the edge opposite to its vertex.
+    let edges = [(v[1], v[2]), (v[2], v[0]), (v[0], v[1])];
+    let bias = edges.map(|(a, b)| if is_top_left(a, b) { 0 } else { -1 });
+    let step_x = edges.map(|(a, b)| (a.1 - b.1) * SUBPIXEL_ONE);
+    let step_y = edges.map(|(a, b)| (b.0 - a.0) * SUBPIXEL_ONE);
+
+    let start = (
+        bounds.min_x as i64 * SUBPIXEL_ONE + SUBPIXEL_ONE / 2,
+        bounds.min_y as i64 * SUBPIXEL_ONE + SUBPIXEL_ONE / 2,
+    );
+    let mut row = edges.map(|(a, b)| orient2d(a, b, start));
+    let inv_area = 1. / area as f32;
+
+    for y in bounds.min_y..bounds.max_y {
+        let mut w = row;
+        for x in bounds.min_x..bounds.max_x {
+            if (0..3).all(|i| w[i] + bias[i] >= 0) {
+                let mut barycentric = [0.; 3];
+                for i in 0..3 {
+                    barycentric[order[i]] = w[i] as f32 * inv_area;
+                }
+
+                // * Depth is already divided by w, so it can be interpolated linearly in screen space.
+                let pz = barycentric[0] * triangle[0].depth
+                    + barycentric[1] * triangle[1].depth
+                    + barycentric[2] * triangle[2].depth;
+                let idx = ((y - viewport.y) * viewport.width + x - viewport.x) as usize;
+
+                if compare.passes(pz, depth_values[idx])
+                    && f(idx, Vec2::new(x as f32 + 0.5, y as f32 + 0.5), barycentric, pz)
+                {
+                    depth_values[idx] = pz;
+                }
+            }
+
+            for i in 0..3 {
+                w[i] += step_x[i];
+            }
+        }
+        for i in 0..3 {
+            row[i] += step_y[i];
+        }
+    }
+}
+
+/// Interpolates the triangle's varyings perspective-correctly, by interpolating
+/// `varying / w` and `1 / w` linearly in screen space and dividing the results.
+// ? https://www.scratchapixel.com/lessons/3d-basic-rendering/rasterization-practical-implementation/perspective-correct-interpolation-vertex-attributes.html
+fn interpolate_varying<V: Varying>(triangle: &[ScreenVertex<V>; 3], weights: [f32; 3]) -> V {
+    let w0 = weights[0] * triangle[0].inv_w;
+    let w1 = weights[1] * triangle[1].inv_w;
+    let w2 = weights[2] * triangle[2].inv_w;
+    let inv_w = w0 + w1 + w2;
+
+    triangle[0].varying.scale(w0)
+        .add(triangle[1].varying.scale(w1))
+        .add(triangle[2].varying.scale(w2))
+        .scale(1. / inv_w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::Camera,
+        depth_buffer::DepthCompare,
+        object::Object,
+        shader::{Lighting, UvShader},
+    };
+    use std::cell::RefCell;
+    use ultraviolet::{Mat4, Rotor3};
+
+    fn vertex(x: f32, y: f32, depth: f32) -> ScreenVertex<()> {
+        ScreenVertex {
+            position: Vec2::new(x, y),
+            depth,
+            inv_w: 1.,
+            varying: (),
+        }
+    }
+
+    /// Overlapping triangles of every size scattered over a `width` by `height` screen, some of
+    /// them reaching past its edges.
+    fn scattered_triangles(count: usize, width: f32, height: f32) -> Vec<[ScreenVertex<()>; 3]> {
+        let mut seed = 0x2545_F491_u32;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as f32 / u32::MAX as f32
+        };
+        (0..count)
+            .map(|_| {
+                let center = Vec2::new(random() * width, random() * height);
+                let size = 1. + random() * width / 2.;
+                [0, 1, 2].map(|_| {
+                    let offset = Vec2::new(random() - 0.5, random() - 0.5) * size;
+                    vertex(center.x + offset.x, center.y + offset.y, random())
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tiled_matches_single_threaded() {
+        let (width, height) = (203, 157);
+        let triangles = scattered_triangles(300, width as f32, height as f32);
+        let shade = |tri_index: usize, fragment: &Fragment<()>| {
+            // * Translucent colours also check that triangles are blended in the same order.
+            let hue = tri_index as f32 / 300.;
+            Some(Vec4::new(hue, 1. - hue, fragment.depth, 0.5 + hue / 2.))
+        };
+
+        let mut serial_frame = FrameBuffer::new(width, height);
+        let mut serial_depth = DepthBuffer::new(width, height, 1., DepthCompare::Less);
+        rasterize_triangles(&mut serial_frame, &mut serial_depth, &triangles, shade);
+        assert!(serial_depth.values.iter().any(|z| *z < 1.));
+
+        for tile_height in [1, 16, 64, 1000] {
+            let mut tiled_frame = FrameBuffer::new(width, height);
+            let mut tiled_depth = DepthBuffer::new(width, height, 1., DepthCompare::Less);
+            rasterize_triangles_tiled(&mut tiled_frame, &mut tiled_depth, &triangles, tile_height, shade);
+
+            assert!(serial_frame.pixels == tiled_frame.pixels, "colours differ with {}px high tiles", tile_height);
+            assert!(serial_depth.values == tiled_depth.values, "depths differ with {}px high tiles", tile_height);
+        }
+    }
+
+    /// Counts how many of `triangles` cover each pixel of a `size` by `size` screen.
+    fn coverage(size: u32, triangles: &[[ScreenVertex<()>; 3]]) -> Vec<u32> {
+        let viewport = Viewport::new(0, 0, size, size);
+        let mut depth_values = vec![0.; (size * size) as usize];
+        let mut counts = vec![0; (size * size) as usize];
+        for triangle in triangles {
+            rasterize(&mut depth_values, DepthCompare::Always, &viewport, triangle, |idx, _, _, _| {
+                counts[idx] += 1;
+                true
+            });
+        }
+        counts
+    }
+
+    #[test]
+    fn shared_edge_is_covered_once() {
+        // * Every corner sits on a pixel center, so the diagonal passes exactly through a row of them.
+        let [a, b, c, d] = [(2.5, 2.5), (18.5, 2.5), (18.5, 18.5), (2.5, 18.5)].map(|(x, y)| vertex(x, y, 0.));
+        let counts = coverage(24, &[[a, b, c], [a, c, d]]);
+
+        assert!(counts.iter().all(|count| *count <= 1));
+        for i in 2..18 {
+            assert_eq!(counts[i * 24 + i], 1, "diagonal pixel {} isn't covered exactly once", i);
+        }
+        // * Only the top and left edges of the square are included.
+        assert_eq!(counts.iter().sum::<u32>(), 16 * 16);
+    }
+
+    #[test]
+    fn triangle_fan_covers_each_pixel_once() {
+        let center = vertex(32.5, 32.5, 0.);
+        let rim = (0..16)
+            .map(|i| {
+                let angle = i as f32 / 16. * std::f32::consts::TAU;
+                // * Snapped to pixel centers, so that many pixels lie exactly on the spokes.
+                vertex((32. + 24. * angle.cos()).round() + 0.5, (32. + 24. * angle.sin()).round() + 0.5, 0.)
+            })
+            .collect::<Vec<_>>();
+        let fan = (0..16).map(|i| [center, rim[i], rim[(i + 1) % 16]]).collect::<Vec<_>>();
+        let counts = coverage(64, &fan);
+
+        assert!(counts.iter().all(|count| *count <= 1));
+        for y in 0..64 {
+            for x in 0..64 {
+                let distance = Vec2::new(x as f32 + 0.5 - 32.5, y as f32 + 0.5 - 32.5).mag();
+                if distance < 20. {
+                    assert_eq!(counts[y * 64 + x], 1, "pixel ({}, {}) inside of the fan isn't covered", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn interpolates_uvs_perspective_correctly() {
+        // * A floor stretching far away from the camera, seen at a grazing angle.
+        let (width, height) = (96, 64);
+        let camera = Camera::new(Vec3::zero(), Rotor3::identity(), 1.2, 0.1, 100., width, height);
+        let quad = Object {
+            vertices: vec![
+                Vec3::new(-1., 1., 1.),
+                Vec3::new(1., 1., 1.),
+                Vec3::new(1., 1., 21.),
+                Vec3::new(-1., 1., 21.),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+            uv_coords: vec![Vec2::new(0., 0.), Vec2::new(1., 0.), Vec2::new(1., 1.), Vec2::new(0., 1.)],
+            ..Object::new("floor")
+        };
+        let lighting = Lighting::default();
+        let uniforms = camera.uniforms(&quad, Mat4::identity(), &lighting);
+        let mut triangles = Vec::new();
+        camera.project_object(&quad, &uniforms, &UvShader, |tri| triangles.push(*tri));
+
+        let fragments = RefCell::new(Vec::new());
+        let mut frame = FrameBuffer::new(width, height);
+        let mut depth_buffer = DepthBuffer::for_camera(&camera);
+        rasterize_triangles(&mut frame, &mut depth_buffer, &triangles, |tri_index, fragment| {
+            let triangle = &triangles[tri_index];
+            let screen_linear = (0..3)
+                .map(|i| triangle[i].varying * fragment.barycentric[i])
+                .fold(Vec2::zero(), |sum, uv| sum + uv);
+            fragments.borrow_mut().push((fragment.position, fragment.varying, screen_linear));
+            Some(Vec4::one())
+        });
+
+        // * Casts a ray through the pixel center and finds where it hits the floor.
+        let inverse_projection = camera.projection_matrix.inversed();
+        let expected_uv = |position: Vec2| {
+            let ndc = Vec2::new(position.x / width as f32, position.y / height as f32) * 2. - Vec2::one();
+            let ray = inverse_projection * Vec4::new(ndc.x, ndc.y, 0.5, 1.);
+            let hit = ray.truncated() / ray.y;
+            Vec2::new((hit.x + 1.) / 2., (hit.z - 1.) / 20.)
+        };
+
+        let fragments = fragments.into_inner();
+        assert!(fragments.len() > 500);
+        let mut max_linear_error = 0f32;
+        for (position, uv, screen_linear) in fragments {
+            let expected = expected_uv(position);
+            assert!((uv - expected).mag() < 1e-3, "uv {:?} at {:?} should be {:?}", uv, position, expected);
+            max_linear_error = max_linear_error.max((screen_linear - expected).mag());
+        }
+        // * Interpolating in screen space would be far off, so the test can tell them apart.
+        assert!(max_linear_error > 0.1);
+    }
+}
@@ -1,148 +1,434 @@
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU32, Ordering};
 
-use crate::{aabb::AABB, camera::Camera, input_manager::InputManager, object::Object};
-use softbuffer::Buffer;
-use ultraviolet::{Rotor3, Vec3, Vec2};
-use winit::{event::VirtualKeyCode, window::{Window, CursorGrabMode}};
+use crate::{
+    camera::Camera,
+    clipping::{clip_line, is_inside, ClipVertex},
+    depth_buffer::DepthBuffer,
+    framebuffer::{color_to_rgb, FrameBuffer},
+    light::Light,
+    rasterizer::{
+        rasterize_line, rasterize_point, rasterize_triangles, rasterize_triangles_tiled, Fragment,
+        Viewport,
+    },
+    scene_graph::SceneGraph,
+    shader::{
+        FlatShader, FragmentShader, GouraudShader, Lighting, NormalShader, PhongShader, Shading,
+        TriangleIdShader, UvShader, VertexShader, WireframeShader,
+    },
+    shadow::{ShadowMap, ShadowSettings},
+};
+use ultraviolet::{Rotor3, Vec3, Vec4};
+
+/// Height of the full-width tiles used by the multithreaded rasterizer, in pixels.
+const TILE_HEIGHT: u32 = 64;
+
+/// How far lines and points are moved towards the camera, as a fraction of their distance from it,
+/// so that they're drawn over the triangles they belong to. See [`Camera::pull_towards_camera`].
+const LINE_DEPTH_BIAS: f32 = 2e-3;
+
+/// What is drawn for each triangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Filled triangles, coloured by the fragment shader.
+    #[default]
+    Solid,
+    /// The edges of the triangles, in [`Scene::wireframe_color`].
+    Wireframe,
+    /// Filled triangles with their edges drawn over them.
+    SolidWireframe,
+    /// The vertices of the triangles, in [`Scene::wireframe_color`].
+    Points,
+}
+
+impl RenderMode {
+    /// The mode after this one, going back to the first after the last.
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Solid => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::SolidWireframe,
+            RenderMode::SolidWireframe => RenderMode::Points,
+            RenderMode::Points => RenderMode::Solid,
+        }
+    }
+}
+
+/// Replaces the shaded image with a visualisation of how it was rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    /// The normal shaded image.
+    #[default]
+    None,
+    /// The depth of each pixel in grayscale, from white for the nearest to black for the furthest.
+    Depth,
+    /// See [`NormalShader`].
+    Normals,
+    /// See [`UvShader`].
+    Uvs,
+    /// How many times each pixel was shaded, from blue for once to red for [`OVERDRAW_MAX`] or more.
+    Overdraw,
+    /// See [`TriangleIdShader`].
+    TriangleId,
+}
+
+impl DebugView {
+    /// The view after this one, going back to the first after the last.
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::None => DebugView::Depth,
+            DebugView::Depth => DebugView::Normals,
+            DebugView::Normals => DebugView::Uvs,
+            DebugView::Uvs => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::TriangleId,
+            DebugView::TriangleId => DebugView::None,
+        }
+    }
+}
+
+/// How many times a pixel has to be shaded to be shown as fully red by [`DebugView::Overdraw`].
+pub const OVERDRAW_MAX: u32 = 8;
 
 pub struct Scene {
-    pub objects: Vec<Object>,
+    pub graph: SceneGraph,
     pub camera: Camera,
-    pub input_manager: InputManager,
-    prev_update_time: Instant,
+    /// Reused between frames. Its clear value and compare function are matched to
+    /// [`Camera::reversed_z`] whenever that changes, and are otherwise left as they're set.
+    pub depth_buffer: DepthBuffer,
+    pub lights: Vec<Light>,
+    /// Light reaching every surface from all directions, regardless of [`Scene::lights`].
+    pub ambient_light: Vec3,
+    pub shadow_settings: ShadowSettings,
+    /// Which of the built-in shaders [`Scene::render`] uses.
+    pub shading: Shading,
+    pub render_mode: RenderMode,
+    pub debug_view: DebugView,
+    /// The colour of lines and points, depending on [`Scene::render_mode`].
+    pub wireframe_color: Vec4,
+    /// Width and height of each point in [`RenderMode::Points`], in pixels.
+    pub point_size: u32,
+    /// Whether to rasterize tiles of the screen in parallel. Produces the same image either way.
+    pub multithreaded: bool,
+    /// How many pixels each triangle of an object should cover on screen, which decides which of
+    /// its [`lods`](crate::object::Object::lods) is rendered. 0 always renders at full detail.
+    pub lod_pixels_per_triangle: f32,
+    /// The shadow map of each of [`Scene::lights`] from the last frame, kept to reuse their buffers.
+    shadow_maps: Vec<Option<ShadowMap>>,
+    /// The [`Camera::reversed_z`] that [`Scene::depth_buffer`] was last matched to.
+    depth_reversed_z: bool,
 }
 
 impl Scene {
-    pub fn new(objects: Vec<Object>, screen_width: u32, screen_height: u32) -> Self {
+    pub fn new(graph: SceneGraph, screen_width: u32, screen_height: u32) -> Self {
+        let camera = Camera::new(
+            Vec3::new(0., 0., -5.),
+            Rotor3::identity(),
+            1.5,
+            0.01,
+            1000.,
+            screen_width,
+            screen_height,
+        );
+        let depth_buffer = DepthBuffer::for_camera(&camera);
+        let depth_reversed_z = camera.reversed_z;
+
         Self {
-            objects,
-            camera: Camera::new(
-                Vec3::new(0., 0., -5.),
-                Rotor3::identity(),
-                1.5,
-                0.01,
-                1000.,
-                screen_width,
-                screen_height,
-            ),
-            input_manager: InputManager::new(),
-            prev_update_time: Instant::now(),
+            graph,
+            camera,
+            depth_buffer,
+            lights: vec![Light::Directional {
+                direction: Vec3::new(0.2, 0.4, 1.).normalized(),
+                color: Vec3::one(),
+                intensity: 1.,
+                casts_shadows: true,
+            }],
+            ambient_light: Vec3::broadcast(0.1),
+            shadow_settings: ShadowSettings::default(),
+            shading: Shading::default(),
+            render_mode: RenderMode::default(),
+            debug_view: DebugView::default(),
+            wireframe_color: Vec4::new(1., 0., 0., 1.),
+            point_size: 3,
+            multithreaded: true,
+            lod_pixels_per_triangle: 16.,
+            shadow_maps: Vec::new(),
+            depth_reversed_z,
         }
     }
 
-    pub fn update_delta_time(&mut self) -> Duration {
-        let now = Instant::now();
-        let prev = std::mem::replace(&mut self.prev_update_time, now.clone());
-        now - prev
-    }
-
-    pub fn update(&mut self, window: &mut Window) {
-        let delta_time = self.update_delta_time().as_millis() as f32;
-        
-        if self.input_manager.is_keycode_held(VirtualKeyCode::Escape) {
-            self.input_manager.cursor_visible = true;
-            self.input_manager.cursor_mode = CursorGrabMode::None;
-        } else if self.input_manager.is_mouse_button_held(winit::event::MouseButton::Left) {
-            self.input_manager.cursor_visible = false;
-            self.input_manager.cursor_mode = CursorGrabMode::Locked;
+    /// Renders the scene into `frame` with the shaders selected by [`Scene::shading`], unless
+    /// [`Scene::debug_view`] replaces them.
+    pub fn render(&mut self, frame: &mut FrameBuffer) {
+        match self.debug_view {
+            DebugView::Normals => return self.render_with(frame, &NormalShader, &NormalShader),
+            DebugView::Uvs => return self.render_with(frame, &UvShader, &UvShader),
+            DebugView::TriangleId => return self.render_with(frame, &TriangleIdShader, &TriangleIdShader),
+            DebugView::None | DebugView::Depth | DebugView::Overdraw => {}
         }
-        window.set_cursor_visible(self.input_manager.cursor_visible);
-        window.set_cursor_grab(self.input_manager.cursor_mode).unwrap();
 
-        if self.input_manager.is_keycode_held(VirtualKeyCode::W) {
-            self.camera.position +=
-                0.001 * delta_time * Vec3::unit_z().rotated_by(self.camera.rotation);
-        }
-        if self.input_manager.is_keycode_held(VirtualKeyCode::S) {
-            self.camera.position -=
-                0.001 * delta_time * Vec3::unit_z().rotated_by(self.camera.rotation);
+        match self.shading {
+            Shading::Flat => self.render_with(frame, &FlatShader, &FlatShader),
+            Shading::Gouraud => self.render_with(frame, &GouraudShader, &GouraudShader),
+            Shading::Phong => self.render_with(frame, &PhongShader, &PhongShader),
+            Shading::Wireframe => {
+                let shader = WireframeShader::default();
+                self.render_with(frame, &shader, &shader);
+            }
         }
+    }
 
-        if self.input_manager.is_keycode_held(VirtualKeyCode::A) {
-            self.camera.position +=
-                0.001 * delta_time * Vec3::unit_x().rotated_by(self.camera.rotation);
-        }
-        if self.input_manager.is_keycode_held(VirtualKeyCode::D) {
-            self.camera.position -=
-                0.001 * delta_time * Vec3::unit_x().rotated_by(self.camera.rotation);
-        }
+    /// Renders the scene into `frame`, running `vertex_shader` on every object's triangles
+    /// and `fragment_shader` on the pixels they cover.
+    pub fn render_with<VS, FS>(&mut self, frame: &mut FrameBuffer, vertex_shader: &VS, fragment_shader: &FS)
+    where
+        VS: VertexShader,
+        FS: FragmentShader<VS::Varying>,
+    {
+        let (width, height) = (frame.width, frame.height);
+        self.camera.update_screen_dimensions(width, height);
+        frame.clear(0);
 
-        if self.input_manager.is_keycode_held(VirtualKeyCode::LControl) {
-            self.camera.position +=
-                0.001 * delta_time * Vec3::unit_y().rotated_by(self.camera.rotation);
+        // * Objects that are small on screen are swapped for their simplified versions.
+        let objects = self
+            .graph
+            .world_objects()
+            .into_iter()
+            .map(|(obj, transform)| {
+                let area = obj
+                    .local_bounds()
+                    .map_or(0., |bounds| self.camera.projected_area(&bounds, transform));
+                (obj.select_lod(area / self.lod_pixels_per_triangle), transform)
+            })
+            .collect::<Vec<_>>();
+        let solid = matches!(self.render_mode, RenderMode::Solid | RenderMode::SolidWireframe);
+        self.shadow_maps.resize_with(self.lights.len(), || None);
+        if solid {
+            for (map, light) in self.shadow_maps.iter_mut().zip(&self.lights) {
+                *map = ShadowMap::render(light, &objects, &self.shadow_settings, map.take());
+            }
         }
-        if self.input_manager.is_keycode_held(VirtualKeyCode::Space) {
-            self.camera.position -=
-                0.001 * delta_time * Vec3::unit_y().rotated_by(self.camera.rotation);
+
+        let lighting = Lighting {
+            lights: &self.lights,
+            shadow_maps: if solid { &self.shadow_maps } else { &[] },
+            shadow_settings: self.shadow_settings,
+            ambient_light: self.ambient_light,
+        };
+        let uniforms = objects
+            .iter()
+            .map(|(obj, transform)| self.camera.uniforms(obj, *transform, &lighting))
+            .collect::<Vec<_>>();
+        let depth_buffer = &mut self.depth_buffer;
+        if self.depth_reversed_z != self.camera.reversed_z {
+            depth_buffer.match_camera(&self.camera);
+            self.depth_reversed_z = self.camera.reversed_z;
         }
+        depth_buffer.resize(width, height);
+        depth_buffer.clear();
+        // * Counted from the shaders, which run on several threads at once.
+        let overdraw = (self.debug_view == DebugView::Overdraw)
+            .then(|| frame.pixels.iter().map(|_| AtomicU32::new(0)).collect::<Vec<_>>());
+
+        if solid {
+            // * Project every triangle up front, so that they can be split between tiles.
+            let mut triangles = Vec::new();
+            let mut triangle_objects = Vec::new();
+            for (obj_index, (obj, _)) in objects.iter().enumerate() {
+                self.camera.project_object(obj, &uniforms[obj_index], vertex_shader, |screen_tri| {
+                    triangles.push(*screen_tri);
+                    triangle_objects.push(obj_index);
+                });
+            }
 
-        if !self.input_manager.cursor_visible {
-            let mouse_delta = self.input_manager.use_mouse_delta();
-            let sensitivity = Vec2::new(0.004, 0.003);
-            self.camera.rotation = Rotor3::from_rotation_xz(mouse_delta.x * sensitivity.x) * self.camera.rotation;
-            // self.camera.rotation = Rotor3::from_rotation_yz(mouse_delta.y * sensitivity.y) * self.camera.rotation;
-            self.camera.rotation.normalize();
+            let shade = |tri_index: usize, fragment: &Fragment<VS::Varying>| {
+                let color = fragment_shader.shade_fragment(&uniforms[triangle_objects[tri_index]], fragment);
+                if let (Some(overdraw), Some(_)) = (&overdraw, color) {
+                    let idx = fragment.position.y as usize * width as usize + fragment.position.x as usize;
+                    overdraw[idx].fetch_add(1, Ordering::Relaxed);
+                }
+                color
+            };
+
+            if self.multithreaded {
+                rasterize_triangles_tiled(frame, depth_buffer, &triangles, TILE_HEIGHT, shade);
+            } else {
+                rasterize_triangles(frame, depth_buffer, &triangles, shade);
+            }
         }
-        
-        // dbg!(self.camera.rotation);
-    }
 
-    pub fn render(&mut self, buffer: &mut Buffer, width: u32, height: u32) {
-        self.camera.update_screen_dimensions(width, height);
-        let camera_space_transform = self.camera.get_local_space_transform();
-        let mut depth_buffer = vec![1.; width as usize * height as usize];
-
-        for obj in &self.objects {
-            let transform = obj.get_transform();
-
-            for tri_indices in &obj.triangles {
-                let tri = obj.get_triangle_unchecked(*tri_indices);
-                let local_tri = tri
-                    .apply_transform(transform)
-                    .apply_transform(camera_space_transform);
-
-                let (screen_tri, z_depth) = self.camera.project_triangle(local_tri);
-                
-                // ? Barycentric coordinates: https://www.desmos.com/calculator/ovebiysjce
-                let v0 = screen_tri.v0;
-                let v1 = screen_tri.v1;
-                let v2 = screen_tri.v2;
-                let wd = (v1.y - v2.y) * (v0.x - v2.x) + (v2.x - v1.x) * (v0.y - v2.y);
-                
-                if wd.is_normal() {
-                    if let Some(tri_aabb) = AABB::from(&screen_tri).intersection(&self.camera.screen_aabb) {
-                        for p in tri_aabb {
-                            let w0 = ((v1.y - v2.y) * (p.x - v2.x) + (v2.x - v1.x) * (p.y - v2.y)) / wd;
-                            let w1 = ((v2.y - v0.y) * (p.x - v2.x) + (v0.x - v2.x) * (p.y - v2.y)) / wd;
-                            let w2 = 1. - w0 - w1;
-                            
-                            if w0 > 0. && w1 > 0. && w2 > 0. {
-                                let pz = w0 * z_depth[0] + w1 * z_depth[1] + w2 * z_depth[2];
-                                let idx = p.y as usize * width as usize + p.x as usize;
-
-                                if pz > 0. && pz.abs() < depth_buffer[idx] {
-                                    depth_buffer[idx] = pz;
-                                    if w0 < 0.01 || w1 < 0.01 || w2 < 0.01 {
-                                        buffer[idx] = 255 << 16;
-                                    } else {
-                                        buffer[idx] = 0;
-                                    }
-                                }
-                            }
+        // * Lines and points are drawn on top, depth tested against the triangles.
+        let viewport = Viewport::new(0, 0, width, height);
+        if self.render_mode != RenderMode::Solid {
+            for (obj_index, (obj, _)) in objects.iter().enumerate() {
+                self.camera.shade_object(obj, &uniforms[obj_index], vertex_shader, |clip_tri| {
+                    let clip_tri = clip_tri.map(|v| ClipVertex {
+                        position: self.camera.pull_towards_camera(v.position, LINE_DEPTH_BIAS),
+                        ..v
+                    });
+                    if self.render_mode == RenderMode::Points {
+                        for vertex in clip_tri.iter().filter(|v| is_inside(v.position)) {
+                            let [point] = self.camera.clip_to_screen([*vertex]);
+                            rasterize_point(
+                                frame,
+                                depth_buffer,
+                                &viewport,
+                                &point,
+                                self.point_size,
+                                self.wireframe_color,
+                            );
                         }
+                        return;
                     }
-                }
+
+                    for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                        if let Some(line) = clip_line([clip_tri[a], clip_tri[b]]) {
+                            let line = self.camera.clip_to_screen(line);
+                            rasterize_line(
+                                frame,
+                                depth_buffer,
+                                &viewport,
+                                &line,
+                                self.wireframe_color,
+                            );
+                        }
+                    }
+                });
             }
         }
 
-        // let max_z = depth_buffer.iter().filter(|v| v.is_finite()).max_by(|a,b| a.total_cmp(b)).unwrap_or(&1.);
-        // let min_z = depth_buffer.iter().filter(|v| v.is_finite()).min_by(|a,b| a.total_cmp(b)).unwrap_or(&0.);
-        // for (i, p) in buffer.iter_mut().enumerate() {
-        //     if *p == 0 {
-        //         let z = depth_buffer[i];
-        //         let v = 255 - (255. * (z - min_z) / (max_z - min_z)) as u32;
-        //         *p = v;
-        //     }
-        // }
+        match (self.debug_view, overdraw) {
+            (DebugView::Depth, _) => show_depth(frame, &self.depth_buffer),
+            (DebugView::Overdraw, Some(overdraw)) => show_overdraw(frame, &overdraw),
+            _ => {}
+        }
+    }
+}
+
+/// Replaces every pixel that was drawn with its depth in grayscale, scaled so that the nearest
+/// pixel is white and the furthest is black.
+fn show_depth(frame: &mut FrameBuffer, depth_buffer: &DepthBuffer) {
+    // * The clear value is the far plane, whichever way around depth goes.
+    let distances = depth_buffer
+        .values
+        .iter()
+        .enumerate()
+        .map(|(idx, z)| depth_buffer.is_drawn(idx).then(|| (z - depth_buffer.clear_value).abs()))
+        .collect::<Vec<_>>();
+    let drawn = || distances.iter().flatten().copied();
+    let min = drawn().min_by(f32::total_cmp).unwrap_or(0.);
+    let max = drawn().max_by(f32::total_cmp).unwrap_or(1.);
+    for (pixel, distance) in frame.pixels.iter_mut().zip(distances) {
+        if let Some(distance) = distance {
+            let brightness = (distance - min) / (max - min).max(f32::EPSILON);
+            *pixel = color_to_rgb(Vec3::broadcast(brightness));
+        }
+    }
+}
+
+/// Replaces every pixel with a heat map colour for how many times it was shaded, leaving pixels
+/// that weren't shaded black.
+fn show_overdraw(frame: &mut FrameBuffer, overdraw: &[AtomicU32]) {
+    let gradient = [
+        Vec3::new(0., 0., 1.),
+        Vec3::new(0., 1., 1.),
+        Vec3::new(0., 1., 0.),
+        Vec3::new(1., 1., 0.),
+        Vec3::new(1., 0., 0.),
+    ];
+    for (pixel, count) in frame.pixels.iter_mut().zip(overdraw) {
+        let count = count.load(Ordering::Relaxed);
+        *pixel = if count == 0 {
+            0
+        } else {
+            let t = (count - 1).min(OVERDRAW_MAX - 1) as f32 / (OVERDRAW_MAX - 1) as f32;
+            let position = t * (gradient.len() - 1) as f32;
+            let i = (position as usize).min(gradient.len() - 2);
+            let t = position - i as f32;
+            color_to_rgb(gradient[i] * (1. - t) + gradient[i + 1] * t)
+        };
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{depth_buffer::DepthCompare, object::tests::cube};
+
+    /// A red cube in front of a larger blue one, as seen from the default camera.
+    fn stacked_cubes() -> SceneGraph {
+        let mut near = cube();
+        near.material.diffuse = Vec3::unit_x();
+        let mut far = cube();
+        far.material.diffuse = Vec3::unit_z();
+        far.position = Vec3::new(-1., -1., 3.);
+        far.scale = Vec3::broadcast(3.);
+
+        let mut graph = SceneGraph::new();
+        graph.add(near, None);
+        graph.add(far, None);
+        graph
+    }
+
+    #[test]
+    fn depth_buffer_follows_reversed_z() {
+        let mut scene = Scene::new(stacked_cubes(), 64, 48);
+        scene.render_mode = RenderMode::SolidWireframe;
+        let mut reversed = FrameBuffer::new(64, 48);
+        scene.render(&mut reversed);
+
+        scene.camera.reversed_z = false;
+        let mut forward = FrameBuffer::new(64, 48);
+        scene.render(&mut forward);
+        assert_eq!(scene.depth_buffer.compare, DepthCompare::Less);
+        assert_eq!(scene.depth_buffer.clear_value, 1.);
+        assert!(forward.pixels == reversed.pixels);
+    }
+
+    #[test]
+    fn keeps_depth_compare_set_on_scene() {
+        let mut scene = Scene::new(stacked_cubes(), 64, 48);
+        let mut nearest = FrameBuffer::new(64, 48);
+        scene.render(&mut nearest);
+
+        // * The far cube is drawn after the near one, so it covers it without depth testing.
+        scene.depth_buffer.compare = DepthCompare::Always;
+        let mut in_order = FrameBuffer::new(64, 48);
+        scene.render(&mut in_order);
+        scene.render(&mut in_order);
+        assert_eq!(scene.depth_buffer.compare, DepthCompare::Always);
+        assert!(in_order.pixels != nearest.pixels);
+
+        scene.depth_buffer.compare = DepthCompare::Greater;
+        let mut again = FrameBuffer::new(64, 48);
+        scene.render(&mut again);
+        assert!(again.pixels == nearest.pixels);
+    }
+
+    #[test]
+    fn hides_back_edges_of_distant_cube() {
+        let mut cube = cube();
+        cube.position = Vec3::new(-10., -10., 55.);
+        cube.scale = Vec3::broadcast(20.);
+        cube.rotation = Rotor3::from_euler_angles(0.2, 0.3, 0.1);
+        let mut graph = SceneGraph::new();
+        let id = graph.add(cube, None);
+        let mut scene = Scene::new(graph, 64, 48);
+        scene.wireframe_color = Vec4::new(0., 1., 0., 1.);
+
+        let mut edge_pixels = |render_mode, cull_back_faces| {
+            scene.render_mode = render_mode;
+            scene.graph.get_mut(id).cull_back_faces = cull_back_faces;
+            let mut frame = FrameBuffer::new(64, 48);
+            scene.render(&mut frame);
+            frame.pixels.iter().filter(|p| **p == color_to_rgb(Vec3::unit_y())).count()
+        };
+
+        // * Culling the back faces of a cube leaves only the edges that should be visible.
+        let all = edge_pixels(RenderMode::Wireframe, false);
+        let visible = edge_pixels(RenderMode::Wireframe, true);
+        let drawn = edge_pixels(RenderMode::SolidWireframe, false);
+        assert!(visible < all);
+        assert!(drawn <= visible, "{drawn} edge pixels drawn, {visible} visible");
+        // * Most of the visible edges still have to be drawn over the faces.
+        assert!(drawn >= visible / 2, "{drawn} edge pixels drawn, {visible} visible");
     }
 }
@@ -0,0 +1,388 @@
+use std::path::Path;
+
+use crate::{
+    error::{AssetError, AssetFormat},
+    object::Object,
+    texture::Texture,
+};
+use image::RgbaImage;
+use ultraviolet::Mat4;
+
+/// Identifies a node of a [`SceneGraph`]. Nodes are never removed, so ids stay valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+struct Node {
+    object: Object,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+/// A hierarchy of [`Object`]s, where each object's transform is relative to its parent's.
+/// Objects without any triangles can be used to group other objects together.
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the default scene of a .gltf or .glb file, or its first scene if it has no default.
+    /// Every glTF node becomes a node of the graph with the same transform. Meshes with a single
+    /// primitive are stored on the node itself, others get a child node for each primitive.
+    pub fn load_from_gltf(path: &str) -> Result<Self, AssetError> {
+        let gltf_error = |e| match e {
+            gltf::Error::Io(source) => AssetError::from_io(path, AssetFormat::Gltf, source),
+            _ => AssetError::Invalid {
+                path: path.into(),
+                format: AssetFormat::Gltf,
+                source: Box::new(e),
+            },
+        };
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(gltf_error)?;
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        let buffers = gltf::import_buffers(&document, Some(base), blob).map_err(gltf_error)?;
+        // * Images are loaded here instead of by `gltf::import`, so that errors name the image that failed.
+        let images = document
+            .images()
+            .map(|image| Texture::load_gltf_image(Path::new(path), &image, &buffers))
+            .collect::<Result<Vec<_>, _>>()?;
+        let scene = document.default_scene().or_else(|| document.scenes().next());
+
+        let mut graph = Self::new();
+        for node in scene.iter().flat_map(|scene| scene.nodes()) {
+            graph.add_gltf_node(&node, None, &buffers, &images);
+        }
+        Ok(graph)
+    }
+
+    fn add_gltf_node(
+        &mut self,
+        node: &gltf::Node,
+        parent: Option<NodeId>,
+        buffers: &[gltf::buffer::Data],
+        images: &[RgbaImage],
+    ) {
+        let name = node.name().unwrap_or_default();
+        let mut primitives = node
+            .mesh()
+            .iter()
+            .flat_map(|mesh| mesh.primitives())
+            .filter_map(|primitive| Object::from_gltf_primitive(name, &primitive, buffers, images))
+            .collect::<Vec<_>>();
+
+        let mut object = match primitives.len() {
+            1 => primitives.pop().unwrap(),
+            _ => Object::new(name),
+        };
+        object.set_transform(Mat4::from(node.transform().matrix()));
+
+        let id = self.add(object, parent);
+        for primitive in primitives {
+            self.add(primitive, Some(id));
+        }
+        for child in node.children() {
+            self.add_gltf_node(&child, Some(id), buffers, images);
+        }
+    }
+
+    /// Adds `object` as a child of `parent`, or as a root if `parent` is `None`.
+    pub fn add(&mut self, object: Object, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            object,
+            parent,
+            children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        id
+    }
+
+    pub fn get(&self, node: NodeId) -> &Object {
+        &self.nodes[node.0].object
+    }
+
+    pub fn get_mut(&mut self, node: NodeId) -> &mut Object {
+        &mut self.nodes[node.0].object
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.ids().filter(|id| self.parent(*id).is_none())
+    }
+
+    /// Returns every node, in the order they were added.
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    /// Returns the first node added whose object is called `name`.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.ids().find(|id| self.get(*id).name == name)
+    }
+
+    /// Returns whether `ancestor` is `node` itself, or one of its parents.
+    pub fn is_ancestor(&self, ancestor: NodeId, node: NodeId) -> bool {
+        let mut current = Some(node);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.parent(id);
+        }
+        false
+    }
+
+    /// Moves `node` and its descendants under `parent`, or to the root if `parent` is `None`.
+    /// If `keep_world_transform` is set, the node's local transform is adjusted so that it
+    /// doesn't move in world space, as far as [`Object::set_transform`] allows. Returns `false`
+    /// without changing anything if `parent` is `node` or one of its descendants.
+    pub fn reparent(&mut self, node: NodeId, parent: Option<NodeId>, keep_world_transform: bool) -> bool {
+        if parent.is_some_and(|parent| self.is_ancestor(node, parent)) {
+            return false;
+        }
+
+        if keep_world_transform {
+            let parent_transform = parent.map_or_else(Mat4::identity, |p| self.world_transform(p));
+            let local = parent_transform.inversed() * self.world_transform(node);
+            self.get_mut(node).set_transform(local);
+        }
+
+        if let Some(old_parent) = self.nodes[node.0].parent {
+            self.nodes[old_parent.0].children.retain(|child| *child != node);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(node);
+        }
+        self.nodes[node.0].parent = parent;
+        true
+    }
+
+    /// Returns the transform from `node`'s local space into world space.
+    pub fn world_transform(&self, node: NodeId) -> Mat4 {
+        let local = self.get(node).get_transform();
+        match self.parent(node) {
+            Some(parent) => self.world_transform(parent) * local,
+            None => local,
+        }
+    }
+
+    /// Returns every object along with its world space transform, parents before children.
+    pub fn world_objects(&self) -> Vec<(&Object, Mat4)> {
+        let mut res = Vec::with_capacity(self.nodes.len());
+        let mut stack = self
+            .roots()
+            .map(|id| (id, Mat4::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((id, parent_transform)) = stack.pop() {
+            let object = self.get(id);
+            let transform = parent_transform * object.get_transform();
+            res.push((object, transform));
+            stack.extend(self.children(id).iter().map(|child| (*child, transform)));
+        }
+        res
+    }
+}
+
+impl From<Vec<Object>> for SceneGraph {
+    /// Adds every object as a root.
+    fn from(objects: Vec<Object>) -> Self {
+        let mut graph = Self::new();
+        for object in objects {
+            graph.add(object, None);
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ultraviolet::{Rotor3, Vec2, Vec3};
+
+    const GLTF_JSON: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "name": "parent", "mesh": 0, "translation": [1, 2, 3], "children": [1] },
+            {
+                "name": "mirrored",
+                "mesh": 1,
+                "translation": [0, 0, 1],
+                "rotation": [0, 0, 0.70710677, 0.70710677],
+                "scale": [-1, 2, 1]
+            }
+        ],
+        "meshes": [
+            { "primitives": [
+                { "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "mode": 5 },
+                { "attributes": { "POSITION": 0 }, "mode": 6 }
+            ] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 2 }] }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC2" },
+            { "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 48 },
+            { "buffer": 0, "byteOffset": 48, "byteLength": 32 },
+            { "buffer": 0, "byteOffset": 80, "byteLength": 6 }
+        ],
+        "buffers": [{ "byteLength": 88 }]
+    }"#;
+
+    const POSITIONS: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]];
+    const UVS: [[f32; 2]; 4] = [[0., 0.], [1., 0.], [0., 1.], [1., 1.]];
+
+    /// A .glb file holding [`GLTF_JSON`], with its buffer in the binary chunk.
+    // ? https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#glb-file-format-specification
+    fn glb_fixture() -> Vec<u8> {
+        let mut json = GLTF_JSON.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = POSITIONS.iter().flatten().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>();
+        bin.extend(UVS.iter().flatten().flat_map(|v| v.to_le_bytes()));
+        bin.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        bin.resize(88, 0);
+
+        let mut data = Vec::new();
+        data.extend(b"glTF");
+        data.extend(2u32.to_le_bytes());
+        data.extend((12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        for (kind, chunk) in [(b"JSON", json), (b"BIN\0", bin)] {
+            data.extend((chunk.len() as u32).to_le_bytes());
+            data.extend(kind);
+            data.extend(chunk);
+        }
+        data
+    }
+
+    fn assert_matrix_eq(a: Mat4, b: Mat4) {
+        let close = a.as_slice().iter().zip(b.as_slice()).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close, "{:?} != {:?}", a, b);
+    }
+
+    /// A chain of 3 nodes, each moved, rotated and scaled relative to its parent.
+    fn chain() -> (SceneGraph, [NodeId; 3]) {
+        let mut graph = SceneGraph::new();
+        let mut parent = None;
+        let ids = [1., 2., 3.].map(|i| {
+            let mut object = Object::new(format!("node {}", i));
+            object.position = Vec3::new(i, 0., -i);
+            object.rotation = Rotor3::from_rotation_xz(0.3 * i);
+            object.scale = Vec3::broadcast(i);
+            let id = graph.add(object, parent);
+            parent = Some(id);
+            id
+        });
+        (graph, ids)
+    }
+
+    #[test]
+    fn reparent_keeps_world_transform() {
+        let (mut graph, [root, middle, leaf]) = chain();
+        let world = graph.world_transform(leaf);
+
+        assert!(graph.reparent(leaf, Some(root), true));
+        assert_eq!(graph.parent(leaf), Some(root));
+        assert_eq!(graph.children(middle), []);
+        assert_eq!(graph.children(root), [middle, leaf]);
+        assert_matrix_eq(graph.world_transform(leaf), world);
+
+        assert!(graph.reparent(leaf, None, true));
+        assert_eq!(graph.parent(leaf), None);
+        assert_matrix_eq(graph.world_transform(leaf), world);
+    }
+
+    #[test]
+    fn reparent_under_descendant_fails() {
+        let (mut graph, [root, middle, leaf]) = chain();
+        let world = graph.world_transform(root);
+
+        assert!(!graph.reparent(root, Some(leaf), true));
+        assert!(!graph.reparent(middle, Some(middle), false));
+        assert_eq!(graph.parent(root), None);
+        assert_eq!(graph.children(leaf), []);
+        assert_eq!(graph.children(root), [middle]);
+        assert_matrix_eq(graph.world_transform(root), world);
+    }
+
+    #[test]
+    fn loads_gltf_scene() {
+        let path = std::env::temp_dir().join("dedede_fixture.glb");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, glb_fixture()).unwrap();
+        let graph = SceneGraph::load_from_gltf(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // * The parent's mesh has 2 primitives, so each of them gets a child node before the glTF child.
+        let parent = graph.find("parent").unwrap();
+        assert!(graph.get(parent).triangles.is_empty());
+        assert_eq!(graph.get(parent).position, Vec3::new(1., 2., 3.));
+        let [strip, fan, mirrored] = graph.children(parent) else {
+            panic!("expected 3 children, found {:?}", graph.children(parent));
+        };
+
+        let strip = graph.get(*strip);
+        assert_eq!(strip.triangles, [[0, 1, 2], [2, 1, 3]]);
+        assert_eq!(strip.vertices, POSITIONS.map(Vec3::from));
+        assert_eq!(strip.uv_coords, UVS.map(|[u, v]| Vec2::new(u, 1. - v)));
+        let fan = graph.get(*fan);
+        assert_eq!(fan.triangles, [[0, 1, 2], [0, 2, 3]]);
+        assert!(fan.uv_coords.is_empty());
+
+        assert_eq!(graph.find("mirrored"), Some(*mirrored));
+        let obj = graph.get(*mirrored);
+        assert_eq!(obj.triangles, [[0, 1, 2]]);
+        // * Mirroring is put on the x axis, which happens to be the one mirrored here.
+        assert!((obj.scale - Vec3::new(-1., 2., 1.)).mag() < 1e-5, "{:?}", obj.scale);
+        let local = Mat4::from_translation(Vec3::unit_z())
+            * Rotor3::from_rotation_xy(std::f32::consts::FRAC_PI_2).into_matrix().into_homogeneous()
+            * Mat4::from_nonuniform_scale(Vec3::new(-1., 2., 1.));
+        assert_matrix_eq(obj.get_transform(), local);
+        assert_matrix_eq(graph.world_transform(*mirrored), Mat4::from_translation(Vec3::new(1., 2., 3.)) * local);
+    }
+
+    #[test]
+    fn names_gltf_texture_that_failed() {
+        let dir = std::env::temp_dir().join("dedede_gltf_textures");
+        std::fs::create_dir_all(&dir).unwrap();
+        let load = |uri: &str| {
+            let json = format!(r#"{{ "asset": {{ "version": "2.0" }}, "images": [{{ "uri": "{}" }}] }}"#, uri);
+            let path = dir.join("textured.gltf");
+            std::fs::write(&path, json).unwrap();
+            SceneGraph::load_from_gltf(path.to_str().unwrap())
+        };
+
+        RgbaImage::new(2, 2).save(dir.join("checker board.png")).unwrap();
+        assert!(load("checker%20board.png").is_ok());
+
+        let missing = dir.join("missing texture.png");
+        match load("missing%20texture.png") {
+            Err(AssetError::MissingTexture { path, .. }) => assert_eq!(path, missing),
+            other => panic!("expected a missing texture, got {:?}", other.err()),
+        }
+
+        std::fs::write(dir.join("broken.png"), b"not a png").unwrap();
+        match load("broken.png") {
+            Err(AssetError::UndecodableTexture { path, .. }) => assert_eq!(path, dir.join("broken.png")),
+            other => panic!("expected an undecodable texture, got {:?}", other.err()),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
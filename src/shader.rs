@@ -0,0 +1,357 @@
+pub use crate::rasterizer::Fragment;
+
+use crate::{
+    light::{IncidentLight, Light},
+    material::Material,
+    shadow::{ShadowMap, ShadowSettings},
+    vertex::VertexAttributes,
+};
+use ultraviolet::{Mat3, Mat4, Vec2, Vec3, Vec4};
+
+/// Values output by a [`VertexShader`] for each vertex, which are interpolated across the
+/// triangle and passed to the [`FragmentShader`]. Interpolation is a weighted sum, so any
+/// value that can be scaled and added together works, including tuples of other varyings.
+pub trait Varying: Copy + Send + Sync {
+    fn scale(self, factor: f32) -> Self;
+    fn add(self, other: Self) -> Self;
+
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self.scale(1. - t).add(other.scale(t))
+    }
+}
+
+impl Varying for () {
+    fn scale(self, _: f32) -> Self {}
+    fn add(self, _: Self) -> Self {}
+}
+
+macro_rules! impl_varying {
+    ($($t:ty),*) => {$(
+        impl Varying for $t {
+            fn scale(self, factor: f32) -> Self {
+                self * factor
+            }
+
+            fn add(self, other: Self) -> Self {
+                self + other
+            }
+        }
+    )*};
+}
+
+impl_varying!(f32, Vec2, Vec3, Vec4, VertexAttributes);
+
+macro_rules! impl_varying_tuple {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t: Varying),*> Varying for ($($t,)*) {
+            fn scale(self, factor: f32) -> Self {
+                ($(self.$i.scale(factor),)*)
+            }
+
+            fn add(self, other: Self) -> Self {
+                ($(self.$i.add(other.$i),)*)
+            }
+        }
+    };
+}
+
+impl_varying_tuple!(A 0, B 1);
+impl_varying_tuple!(A 0, B 1, C 2);
+impl_varying_tuple!(A 0, B 1, C 2, D 3);
+
+/// The lights of a scene, as seen by shaders.
+#[derive(Clone, Copy)]
+pub struct Lighting<'a> {
+    pub lights: &'a [Light],
+    /// The shadow map of each of [`Lighting::lights`], if it casts shadows.
+    pub shadow_maps: &'a [Option<ShadowMap>],
+    pub shadow_settings: ShadowSettings,
+    /// Light reaching every surface from all directions.
+    pub ambient_light: Vec3,
+}
+
+impl Default for Lighting<'_> {
+    fn default() -> Self {
+        Self {
+            lights: &[],
+            shadow_maps: &[],
+            shadow_settings: ShadowSettings::default(),
+            ambient_light: Vec3::zero(),
+        }
+    }
+}
+
+impl Lighting<'_> {
+    /// Returns the light reaching the world space `position` from each light, dimmed by its shadows.
+    pub fn incident_light(&self, position: Vec3) -> impl Iterator<Item = IncidentLight> + '_ {
+        let shadow_maps = self.shadow_maps.iter().map(Option::as_ref).chain(std::iter::repeat(None));
+        self.lights.iter().zip(shadow_maps).filter_map(move |(light, shadow_map)| {
+            let mut incident = light.illuminate(position)?;
+            if let Some(shadow_map) = shadow_map {
+                incident.radiance *= shadow_map.visibility(position, incident.direction, &self.shadow_settings);
+            }
+            Some(incident)
+        })
+    }
+}
+
+/// The values shared by every vertex and fragment of an object while it's being rendered.
+#[derive(Clone, Copy)]
+pub struct Uniforms<'a> {
+    /// Takes the object from its local space into world space.
+    pub model: Mat4,
+    /// Takes the object's normals into world space, see [`normal_matrix`](crate::object::normal_matrix).
+    pub normal_matrix: Mat3,
+    /// Takes world space positions into clip space.
+    pub view_projection: Mat4,
+    pub camera_position: Vec3,
+    pub material: &'a Material,
+    pub lighting: &'a Lighting<'a>,
+}
+
+impl Uniforms<'_> {
+    /// Shades a world space surface point with the object's material and the scene's lights.
+    pub fn shade(&self, attributes: &VertexAttributes) -> Vec4 {
+        let view_direction = (self.camera_position - attributes.position).normalized();
+        self.material.shade(
+            attributes,
+            view_direction,
+            self.lighting.ambient_light,
+            self.lighting.incident_light(attributes.position),
+        )
+    }
+
+    /// Transforms vertex attributes from the object's local space into world space.
+    pub fn to_world(&self, attributes: &VertexAttributes) -> VertexAttributes {
+        VertexAttributes {
+            position: self.model.transform_point3(attributes.position),
+            normal: (self.normal_matrix * attributes.normal).normalized(),
+            ..*attributes
+        }
+    }
+
+    /// Projects a world space position into clip space.
+    pub fn to_clip(&self, position: Vec3) -> Vec4 {
+        self.view_projection * position.into_homogeneous_point()
+    }
+}
+
+/// A corner of a triangle, as passed to a [`VertexShader`]. Everything is in the object's local space.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexInput {
+    pub attributes: VertexAttributes,
+    /// The unit normal of the triangle, facing the same way as the object's front faces.
+    pub face_normal: Vec3,
+    /// Which corner of the triangle this is, from 0 to 2.
+    pub corner: usize,
+    /// The index of the triangle in the object's [`triangles`](crate::object::Object::triangles).
+    pub triangle: usize,
+}
+
+/// Runs for each corner of every triangle, placing it in clip space and computing the values
+/// to interpolate across the triangle for the [`FragmentShader`].
+pub trait VertexShader: Sync {
+    type Varying: Varying;
+
+    /// Returns the vertex's clip space position, see [`Camera`](crate::camera::Camera) for
+    /// its conventions, along with its varyings.
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, Self::Varying);
+}
+
+/// Runs for every pixel covered by a triangle that passes the depth test, returning its RGBA
+/// colour, or `None` to discard it without writing to the colour or depth buffer.
+pub trait FragmentShader<V: Varying>: Sync {
+    fn shade_fragment(&self, uniforms: &Uniforms, fragment: &Fragment<V>) -> Option<Vec4>;
+}
+
+/// The built-in shaders, selected by [`Scene::shading`](crate::scene::Scene::shading).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shading {
+    /// See [`FlatShader`].
+    Flat,
+    /// See [`GouraudShader`].
+    Gouraud,
+    /// See [`PhongShader`].
+    #[default]
+    Phong,
+    /// See [`WireframeShader`].
+    Wireframe,
+}
+
+/// Only places vertices in clip space, for rendering depth without any colour.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthShader;
+
+impl VertexShader for DepthShader {
+    type Varying = ();
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, ()) {
+        (uniforms.to_clip(uniforms.model.transform_point3(vertex.attributes.position)), ())
+    }
+}
+
+/// Lights each pixel using the normal of its triangle, giving a faceted look.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatShader;
+
+impl VertexShader for FlatShader {
+    type Varying = VertexAttributes;
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, VertexAttributes) {
+        let attributes = uniforms.to_world(&VertexAttributes {
+            normal: vertex.face_normal,
+            ..vertex.attributes
+        });
+        (uniforms.to_clip(attributes.position), attributes)
+    }
+}
+
+impl FragmentShader<VertexAttributes> for FlatShader {
+    fn shade_fragment(&self, uniforms: &Uniforms, fragment: &Fragment<VertexAttributes>) -> Option<Vec4> {
+        Some(uniforms.shade(&fragment.varying))
+    }
+}
+
+/// Lights each vertex, and blends the resulting colours across the triangle. Cheaper than
+/// [`PhongShader`], but textures are only sampled at the vertices and highlights get smeared.
+// ? https://en.wikipedia.org/wiki/Gouraud_shading
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GouraudShader;
+
+impl VertexShader for GouraudShader {
+    type Varying = Vec4;
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, Vec4) {
+        let attributes = uniforms.to_world(&vertex.attributes);
+        (uniforms.to_clip(attributes.position), uniforms.shade(&attributes))
+    }
+}
+
+impl FragmentShader<Vec4> for GouraudShader {
+    fn shade_fragment(&self, _: &Uniforms, fragment: &Fragment<Vec4>) -> Option<Vec4> {
+        Some(fragment.varying)
+    }
+}
+
+/// Lights each pixel using the normal interpolated from its triangle's vertices.
+// ? https://en.wikipedia.org/wiki/Phong_shading
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhongShader;
+
+impl VertexShader for PhongShader {
+    type Varying = VertexAttributes;
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, VertexAttributes) {
+        let attributes = uniforms.to_world(&vertex.attributes);
+        (uniforms.to_clip(attributes.position), attributes)
+    }
+}
+
+impl FragmentShader<VertexAttributes> for PhongShader {
+    fn shade_fragment(&self, uniforms: &Uniforms, fragment: &Fragment<VertexAttributes>) -> Option<Vec4> {
+        Some(uniforms.shade(&fragment.varying))
+    }
+}
+
+/// Draws the edges of each triangle. Triangles that are clipped by the near plane also get
+/// edges along it.
+#[derive(Debug, Clone, Copy)]
+pub struct WireframeShader {
+    pub line_color: Vec4,
+    /// How close pixels have to be to an edge to be part of it, in pixels.
+    pub line_width: f32,
+    /// The colour of the rest of each triangle, which hides the edges behind it.
+    /// Pixels that aren't on an edge are discarded if `None`.
+    pub fill_color: Option<Vec4>,
+}
+
+impl Default for WireframeShader {
+    fn default() -> Self {
+        Self {
+            line_color: Vec4::new(1., 0., 0., 1.),
+            line_width: 1.,
+            fill_color: Some(Vec4::new(0., 0., 0., 1.)),
+        }
+    }
+}
+
+impl VertexShader for WireframeShader {
+    type Varying = ();
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, ()) {
+        DepthShader.shade_vertex(uniforms, vertex)
+    }
+}
+
+impl FragmentShader<()> for WireframeShader {
+    fn shade_fragment(&self, _: &Uniforms, fragment: &Fragment<()>) -> Option<Vec4> {
+        if fragment.edge_distances.iter().any(|d| *d < self.line_width) {
+            Some(self.line_color)
+        } else {
+            self.fill_color
+        }
+    }
+}
+
+/// Colours each pixel with its world space normal, mapping each axis from `-1..=1` to `0..=1`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalShader;
+
+impl VertexShader for NormalShader {
+    type Varying = Vec3;
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, Vec3) {
+        let attributes = uniforms.to_world(&vertex.attributes);
+        (uniforms.to_clip(attributes.position), attributes.normal)
+    }
+}
+
+impl FragmentShader<Vec3> for NormalShader {
+    fn shade_fragment(&self, _: &Uniforms, fragment: &Fragment<Vec3>) -> Option<Vec4> {
+        let normal = fragment.varying.normalized();
+        Some((normal * 0.5 + Vec3::broadcast(0.5)).into_homogeneous_point())
+    }
+}
+
+/// Colours each pixel with its UV coordinates, U in red and V in green. Coordinates outside of
+/// `0..=1` wrap around, as they do when sampling textures.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UvShader;
+
+impl VertexShader for UvShader {
+    type Varying = Vec2;
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, Vec2) {
+        let position = uniforms.model.transform_point3(vertex.attributes.position);
+        (uniforms.to_clip(position), vertex.attributes.uv)
+    }
+}
+
+impl FragmentShader<Vec2> for UvShader {
+    fn shade_fragment(&self, _: &Uniforms, fragment: &Fragment<Vec2>) -> Option<Vec4> {
+        let uv = fragment.varying;
+        Some(Vec4::new(uv.x.rem_euclid(1.), uv.y.rem_euclid(1.), 0., 1.))
+    }
+}
+
+/// Colours each triangle with a colour picked from its index, to show how an object is split up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TriangleIdShader;
+
+impl VertexShader for TriangleIdShader {
+    type Varying = Vec4;
+
+    fn shade_vertex(&self, uniforms: &Uniforms, vertex: &VertexInput) -> (Vec4, Vec4) {
+        // * Every corner gets the same colour, so it stays constant across the triangle.
+        let hash = (vertex.triangle as u32).wrapping_mul(0x9E37_79B9);
+        let color = Vec3::new((hash >> 24) as f32, (hash >> 16 & 0xFF) as f32, (hash >> 8 & 0xFF) as f32);
+        let position = uniforms.model.transform_point3(vertex.attributes.position);
+        (uniforms.to_clip(position), (color / 255.).into_homogeneous_point())
+    }
+}
+
+impl FragmentShader<Vec4> for TriangleIdShader {
+    fn shade_fragment(&self, _: &Uniforms, fragment: &Fragment<Vec4>) -> Option<Vec4> {
+        Some(fragment.varying)
+    }
+}
@@ -0,0 +1,176 @@
+use std::f32::consts::PI;
+
+use crate::{
+    camera::Camera,
+    depth_buffer::DepthBuffer,
+    light::Light,
+    object::Object,
+    rasterizer::{rasterize_depth, Viewport},
+    shader::{DepthShader, Lighting},
+};
+use ultraviolet::{Mat4, Rotor3, Vec3};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    /// Width and height of each shadow map, in texels.
+    pub resolution: u32,
+    /// Distance in world units that surfaces are offset towards the light before the shadow
+    /// lookup, which stops them from shadowing themselves ("shadow acne").
+    pub bias: f32,
+    /// Radius in texels of the percentage-closer filtering kernel, where 0 disables filtering.
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.02,
+            pcf_radius: 1,
+        }
+    }
+}
+
+/// The depth of a scene as seen from a shadow-casting [`Light`].
+pub struct ShadowMap {
+    camera: Camera,
+    depth_buffer: DepthBuffer,
+}
+
+impl ShadowMap {
+    /// Renders the depth of `objects`, given with their world space transforms, from the point
+    /// of view of `light`. Returns `None` if the light doesn't cast shadows or there is nothing
+    /// to cast them. The depth buffer of `previous`, the light's map from the last frame, is
+    /// reused if there is one.
+    pub fn render(
+        light: &Light,
+        objects: &[(&Object, Mat4)],
+        settings: &ShadowSettings,
+        previous: Option<Self>,
+    ) -> Option<Self> {
+        let (center, radius) = world_bounds(objects)?;
+        let resolution = settings.resolution;
+
+        let camera = match *light {
+            Light::Directional { direction, casts_shadows: true, .. } => {
+                // * An orthographic camera just outside of the scene's bounding sphere.
+                let position = center - direction.normalized() * 2. * radius;
+                let mut camera = Camera::new(
+                    position,
+                    look_rotation(direction),
+                    0.,
+                    0.5 * radius,
+                    3.5 * radius,
+                    resolution,
+                    resolution,
+                );
+                camera.orthographic_height = Some(2. * radius);
+                camera.update_screen_dimensions(resolution, resolution);
+                camera
+            }
+            Light::Spot { position, direction, outer_angle, casts_shadows: true, .. } => Camera::new(
+                position,
+                look_rotation(direction),
+                (2. * outer_angle).min(PI - 0.01),
+                0.05,
+                (center - position).mag() + radius,
+                resolution,
+                resolution,
+            ),
+            _ => return None,
+        };
+
+        let viewport = Viewport::new(0, 0, resolution, resolution);
+        let mut depth_buffer = previous.map_or_else(|| DepthBuffer::for_camera(&camera), |map| map.depth_buffer);
+        depth_buffer.resize(resolution, resolution);
+        depth_buffer.clear();
+        let lighting = Lighting::default();
+        for (obj, transform) in objects {
+            let uniforms = camera.uniforms(obj, *transform, &lighting);
+            camera.project_object(obj, &uniforms, &DepthShader, |tri| {
+                rasterize_depth(&mut depth_buffer, &viewport, tri)
+            });
+        }
+
+        Some(Self { camera, depth_buffer })
+    }
+
+    /// Returns how much of the light reaches the world space `position`, from 0 (fully shadowed)
+    /// to 1 (fully lit). `to_light` is a unit vector pointing from `position` towards the light.
+    // ? Percentage-closer filtering: https://developer.nvidia.com/gpugems/gpugems/part-ii-lighting-and-shadows/chapter-11-shadow-map-antialiasing
+    pub fn visibility(&self, position: Vec3, to_light: Vec3, settings: &ShadowSettings) -> f32 {
+        let Some(p) = self.camera.world_to_screen(position + to_light * settings.bias) else {
+            return 1.;
+        };
+
+        let size = self.camera.screen_width as i64;
+        let radius = settings.pcf_radius as i64;
+        let (cx, cy) = (p.x.floor() as i64, p.y.floor() as i64);
+
+        let mut lit = 0;
+        for y in cy - radius..=cy + radius {
+            for x in cx - radius..=cx + radius {
+                let outside = x < 0 || y < 0 || x >= size || y >= size;
+                // * Lit unless something nearer to the light was drawn at this texel.
+                let idx = (y * size + x) as usize;
+                if outside || !self.depth_buffer.compare.passes(self.depth_buffer.values[idx], p.z) {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / ((2 * radius + 1) * (2 * radius + 1)) as f32
+    }
+}
+
+/// Rotates the camera's +z look direction onto `direction`.
+fn look_rotation(direction: Vec3) -> Rotor3 {
+    let direction = direction.normalized();
+    if direction.dot(Vec3::unit_z()) < -0.9999 {
+        Rotor3::from_rotation_xz(PI)
+    } else {
+        Rotor3::from_rotation_between(Vec3::unit_z(), direction)
+    }
+}
+
+/// Returns the center and radius of a sphere containing every vertex of `objects` in world space.
+fn world_bounds(objects: &[(&Object, Mat4)]) -> Option<(Vec3, f32)> {
+    let mut vertices = objects.iter().flat_map(|(obj, transform)| {
+        obj.vertices.iter().map(move |v| transform.transform_point3(*v))
+    });
+
+    let first = vertices.next()?;
+    let (min, max) = vertices.fold((first, first), |(min, max), v| (min.min_by_component(v), max.max_by_component(v)));
+    let radius = ((max - min).mag() / 2.).max(f32::EPSILON);
+    Some(((min + max) / 2., radius))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::tests::cube;
+
+    #[test]
+    fn reused_map_matches_fresh_one() {
+        let light = Light::Directional {
+            direction: Vec3::new(0.2, 0.4, 1.).normalized(),
+            color: Vec3::one(),
+            intensity: 1.,
+            casts_shadows: true,
+        };
+        let settings = ShadowSettings { resolution: 64, ..ShadowSettings::default() };
+        let cube = cube();
+        let one = [(&cube, Mat4::identity())];
+        let two = [(&cube, Mat4::identity()), (&cube, Mat4::from_translation(Vec3::new(3., 0., 0.)))];
+
+        let fresh = ShadowMap::render(&light, &one, &settings, None).unwrap();
+        let previous = ShadowMap::render(&light, &two, &settings, None);
+        let reused = ShadowMap::render(&light, &one, &settings, previous).unwrap();
+        assert!(fresh.depth_buffer.values.iter().any(|z| *z != fresh.depth_buffer.clear_value));
+        assert!(reused.depth_buffer.values == fresh.depth_buffer.values);
+
+        // * Growing the resolution resizes the reused buffer.
+        let settings = ShadowSettings { resolution: 128, ..settings };
+        let resized = ShadowMap::render(&light, &one, &settings, Some(reused)).unwrap();
+        assert_eq!(resized.depth_buffer.values.len(), 128 * 128);
+    }
+}
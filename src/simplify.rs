@@ -0,0 +1,368 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::object::Object;
+use ultraviolet::{DVec3, Vec3};
+
+/// The sum of squared distances to a set of planes, as a symmetric 4x4 matrix.
+// ? https://www.cs.cmu.edu/~./garland/Papers/quadrics.pdf
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    /// The upper triangle of the matrix: `aa ab ac ad bb bc bd cc cd dd`.
+    m: [f64; 10],
+}
+
+impl Quadric {
+    /// The quadric of the plane through `point` with the unit `normal`.
+    fn from_plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(point);
+        let m = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d];
+        Self { m: m.map(|v| v * weight) }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut m = self.m;
+        for (a, b) in m.iter_mut().zip(other.m) {
+            *a += b;
+        }
+        Self { m }
+    }
+
+    fn error(&self, p: DVec3) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.m;
+        let (x, y, z) = (p.x, p.y, p.z);
+        aa * x * x + 2. * ab * x * y + 2. * ac * x * z + 2. * ad * x
+            + bb * y * y + 2. * bc * y * z + 2. * bd * y
+            + cc * z * z + 2. * cd * z
+            + dd
+    }
+
+    /// Returns the position with the least error, or `None` if there isn't a single one,
+    /// such as when all of the planes are parallel.
+    fn optimal(&self) -> Option<DVec3> {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, _] = self.m;
+        let det = aa * (bb * cc - bc * bc) - ab * (ab * cc - bc * ac) + ac * (ab * bc - bb * ac);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        // * Cramer's rule for the gradient being zero.
+        let (rx, ry, rz) = (-ad, -bd, -cd);
+        let x = rx * (bb * cc - bc * bc) - ab * (ry * cc - bc * rz) + ac * (ry * bc - bb * rz);
+        let y = aa * (ry * cc - rz * bc) - rx * (ab * cc - bc * ac) + ac * (ab * rz - ry * ac);
+        let z = aa * (bb * rz - bc * ry) - ab * (ab * rz - ry * ac) + rx * (ab * bc - bb * ac);
+        Some(DVec3::new(x, y, z) / det)
+    }
+}
+
+/// A possible collapse of the edge between 2 groups of vertices, which is only still valid
+/// if neither group has changed since, as tracked by their versions.
+struct Collapse {
+    cost: f64,
+    groups: [usize; 2],
+    versions: [u32; 2],
+    position: DVec3,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed, so that the cheapest collapse is at the top of a [`BinaryHeap`].
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// How much more the planes along boundary edges count than those of faces, which stops
+/// the outline of open meshes from shrinking.
+const BOUNDARY_WEIGHT: f64 = 1000.;
+
+impl Object {
+    /// Returns a copy of the object with its triangle count reduced to about `target_triangles`,
+    /// by repeatedly collapsing the edge whose removal changes the surface the least.
+    ///
+    /// Vertices at exactly the same position, such as those split along UV seams or creases,
+    /// are moved together so that the surface doesn't tear. Each vertex keeps its own normal,
+    /// UV coordinates and colour.
+    pub fn simplified(&self, target_triangles: usize) -> Object {
+        let mut res = self.clone();
+        res.lods = Vec::new();
+        if self.triangles.len() <= target_triangles {
+            return res;
+        }
+
+        let vertex_count = self.vertices.len();
+        if self.normals.len() != vertex_count {
+            // * Without vertex normals, the renderer falls back to the simplified faces' normals.
+            res.normals = Vec::new();
+        }
+
+        // * Vertices at the same position form a group, identified by one of its vertices.
+        // * Edges are collapsed between groups rather than between vertices.
+        let mut group = self.canonical_vertices();
+        let mut members = vec![Vec::new(); vertex_count];
+        for (v, g) in group.iter().enumerate() {
+            members[*g].push(v);
+        }
+
+        let mut positions = self
+            .vertices
+            .iter()
+            .map(|v| DVec3::new(v.x as f64, v.y as f64, v.z as f64))
+            .collect::<Vec<_>>();
+        let triangles = &self.triangles;
+        let mut triangle_alive = vec![true; triangles.len()];
+        let mut alive_count = triangles.len();
+        let mut group_triangles = vec![Vec::new(); vertex_count];
+        for (tri_index, indices) in triangles.iter().enumerate() {
+            for i in indices {
+                group_triangles[group[*i]].push(tri_index);
+            }
+        }
+
+        let face_normal = |positions: &[DVec3], [a, b, c]: [usize; 3]| {
+            (positions[b] - positions[a]).cross(positions[c] - positions[a])
+        };
+
+        let original_normals = triangles
+            .iter()
+            .map(|indices| face_normal(&positions, indices.map(|i| group[i])))
+            .collect::<Vec<_>>();
+
+        let mut quadrics = vec![Quadric::default(); vertex_count];
+        for indices in triangles {
+            let groups = indices.map(|i| group[i]);
+            let normal = face_normal(&positions, groups);
+            let area = normal.mag();
+            if area <= 0. {
+                continue;
+            }
+            let quadric = Quadric::from_plane(normal / area, positions[groups[0]], area);
+            for g in groups {
+                quadrics[g] = quadrics[g].add(&quadric);
+            }
+        }
+
+        // * Boundary edges only belong to 1 triangle, and get a plane perpendicular to it.
+        for (tri_index, indices) in triangles.iter().enumerate() {
+            let groups = indices.map(|i| group[i]);
+            for corner in 0..3 {
+                let (a, b) = (groups[corner], groups[(corner + 1) % 3]);
+                let shared = group_triangles[a].iter().any(|other| {
+                    *other != tri_index && triangles[*other].iter().any(|i| group[*i] == b)
+                });
+                if shared {
+                    continue;
+                }
+                let edge = positions[b] - positions[a];
+                let plane_normal = edge.cross(face_normal(&positions, groups));
+                if plane_normal.mag_sq() <= 0. {
+                    continue;
+                }
+                let weight = BOUNDARY_WEIGHT * edge.mag_sq();
+                let quadric = Quadric::from_plane(plane_normal.normalized(), positions[a], weight);
+                quadrics[a] = quadrics[a].add(&quadric);
+                quadrics[b] = quadrics[b].add(&quadric);
+            }
+        }
+
+        let mut versions = vec![0u32; vertex_count];
+        let plan = |a: usize, b: usize, positions: &[DVec3], quadrics: &[Quadric], versions: &[u32]| {
+            let quadric = quadrics[a].add(&quadrics[b]);
+            let mut candidates = vec![positions[a], positions[b], (positions[a] + positions[b]) / 2.];
+            candidates.extend(quadric.optimal());
+            let position = candidates
+                .into_iter()
+                .min_by(|x, y| quadric.error(*x).total_cmp(&quadric.error(*y)))
+                .unwrap();
+            Collapse {
+                cost: quadric.error(position),
+                groups: [a, b],
+                versions: [versions[a], versions[b]],
+                position,
+            }
+        };
+
+        let mut heap = BinaryHeap::new();
+        for indices in triangles {
+            let groups = indices.map(|i| group[i]);
+            for corner in 0..3 {
+                let (a, b) = (groups[corner], groups[(corner + 1) % 3]);
+                if a < b {
+                    heap.push(plan(a, b, &positions, &quadrics, &versions));
+                }
+            }
+        }
+
+        while alive_count > target_triangles {
+            let Some(collapse) = heap.pop() else { break };
+            let [a, b] = collapse.groups;
+            if collapse.versions != [versions[a], versions[b]] {
+                continue;
+            }
+
+            // * Collapses that would turn any of the remaining triangles away from the way they
+            // * originally faced are skipped, so that they can't flip over a few collapses at a time.
+            let flips = [a, b].iter().flat_map(|g| &group_triangles[*g]).any(|tri_index| {
+                let groups = triangles[*tri_index].map(|i| group[i]);
+                if !triangle_alive[*tri_index] || (groups.contains(&a) && groups.contains(&b)) {
+                    return false;
+                }
+                let moved = groups.map(|g| if g == a || g == b { collapse.position } else { positions[g] });
+                let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+                original_normals[*tri_index].dot(after) <= 0.
+            });
+            if flips {
+                continue;
+            }
+
+            // * `b` is merged into `a`.
+            positions[a] = collapse.position;
+            quadrics[a] = quadrics[a].add(&quadrics[b]);
+            versions[a] += 1;
+            versions[b] += 1;
+            for v in std::mem::take(&mut members[b]) {
+                group[v] = a;
+                members[a].push(v);
+            }
+
+            for tri_index in std::mem::take(&mut group_triangles[b]) {
+                if !triangle_alive[tri_index] {
+                    continue;
+                }
+                let groups = triangles[tri_index].map(|i| group[i]);
+                if groups[0] == groups[1] || groups[1] == groups[2] || groups[2] == groups[0] {
+                    triangle_alive[tri_index] = false;
+                    alive_count -= 1;
+                } else {
+                    group_triangles[a].push(tri_index);
+                }
+            }
+            group_triangles[a].retain(|tri_index| triangle_alive[*tri_index]);
+
+            let mut neighbours = group_triangles[a]
+                .iter()
+                .flat_map(|tri_index| triangles[*tri_index].map(|i| group[i]))
+                .filter(|g| *g != a)
+                .collect::<Vec<_>>();
+            neighbours.sort_unstable();
+            neighbours.dedup();
+            for n in neighbours {
+                heap.push(plan(a, n, &positions, &quadrics, &versions));
+            }
+        }
+
+        // * Remove the vertices that no longer belong to any triangle.
+        let mut remap = vec![usize::MAX; vertex_count];
+        let mut kept = Vec::new();
+        res.triangles = triangles
+            .iter()
+            .zip(&triangle_alive)
+            .filter(|(_, alive)| **alive)
+            .map(|(indices, _)| {
+                indices.map(|i| {
+                    if remap[i] == usize::MAX {
+                        remap[i] = kept.len();
+                        kept.push(i);
+                    }
+                    remap[i]
+                })
+            })
+            .collect();
+
+        res.vertices = kept
+            .iter()
+            .map(|i| {
+                let p = positions[group[*i]];
+                Vec3::new(p.x as f32, p.y as f32, p.z as f32)
+            })
+            .collect();
+        res.invalidate_bounds();
+        if res.normals.len() == vertex_count {
+            res.normals = kept.iter().map(|i| self.normals[*i]).collect();
+        }
+        if self.uv_coords.len() == vertex_count {
+            res.uv_coords = kept.iter().map(|i| self.uv_coords[*i]).collect();
+        }
+        if self.colors.len() == vertex_count {
+            res.colors = kept.iter().map(|i| self.colors[*i]).collect();
+        }
+        res
+    }
+
+    /// Fills [`Object::lods`] with `levels` simplified copies of the object, each with half
+    /// as many triangles as the one before it.
+    pub fn generate_lods(&mut self, levels: usize) {
+        let mut lods = Vec::with_capacity(levels);
+        let mut target = self.triangles.len();
+        for _ in 0..levels {
+            target /= 2;
+            let lod = lods.last().unwrap_or(&*self).simplified(target);
+            lods.push(lod);
+        }
+        self.lods = lods;
+    }
+
+    /// Returns the least detailed of the object and its [`Object::lods`] that still has at least
+    /// `triangle_budget` triangles, or the most detailed one if none do.
+    pub fn select_lod(&self, triangle_budget: f32) -> &Object {
+        self.lods
+            .iter()
+            .rev()
+            .find(|lod| lod.triangles.len() as f32 >= triangle_budget)
+            .unwrap_or(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::{tests::cube, Object};
+    use ultraviolet::Vec3;
+
+    #[test]
+    fn reaches_target_without_flipping() {
+        // * A rounded cube, which is closed so every collapse removes exactly 2 triangles.
+        let obj = cube().subdivided(2, &[]);
+        assert_eq!(obj.triangles.len(), 192);
+
+        for target in [190, 96, 40, 8] {
+            let simplified = obj.simplified(target);
+            assert_eq!(simplified.triangles.len(), target);
+            assert!(simplified.validate().is_valid());
+            // * The shape stays convex around its center, so every triangle has to face away from it.
+            for indices in &simplified.triangles {
+                let tri = simplified.get_triangle_unchecked(*indices);
+                let center = (tri.v0 + tri.v1 + tri.v2) / 3.;
+                assert!(tri.cross().dot(center - Vec3::broadcast(0.5)) > 0., "flipped with {} triangles", target);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_faces_pointing_along_their_normals() {
+        // * Simplified vertices keep their original smooth normals, which a face folding over
+        // * would end up pointing against.
+        let obj = Object::load_from_stl("test models/basics/suzanne.stl").unwrap();
+        for target in [obj.triangles.len() / 2, obj.triangles.len() / 8] {
+            let simplified = obj.simplified(target);
+            assert!(simplified.triangles.len() <= target);
+            for (tri_index, indices) in simplified.triangles.iter().enumerate() {
+                let normals = simplified.get_vertex_attributes(tri_index).map(|v| v.normal);
+                let face_normal = simplified.get_triangle_unchecked(*indices).cross();
+                let vertex_normal = normals[0] + normals[1] + normals[2];
+                assert!(face_normal.dot(vertex_normal) > 0., "flipped with {} triangles", target);
+            }
+        }
+    }
+}
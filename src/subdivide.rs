@@ -0,0 +1,275 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
+
+use crate::{mesh::DEFAULT_CREASE_ANGLE, object::Object};
+use ultraviolet::Vec3;
+
+impl Object {
+    /// Returns a copy of the object smoothed by `levels` iterations of Loop subdivision, which
+    /// splits every triangle into 4. UV coordinates, colours and per-vertex normals are
+    /// interpolated linearly, so UV seams and authored normals stay as they were. Objects without
+    /// per-vertex normals get smooth ones afterwards, see [`Object::compute_smooth_normals`],
+    /// which aren't smoothed across `sharp_edges`.
+    ///
+    /// `sharp_edges` are pairs of vertex indices whose edges stay sharp, along with the edges
+    /// around holes. Vertices at exactly the same position are treated as one, so an edge only
+    /// has to be marked once even if its vertices are split, such as along UV seams.
+    // ? https://www.microsoft.com/en-us/research/publication/smooth-subdivision-surfaces-based-on-triangles/
+    pub fn subdivided(&self, levels: usize, sharp_edges: &[[usize; 2]]) -> Object {
+        let mut res = self.clone();
+        res.lods = Vec::new();
+        let mut sharp_edges = sharp_edges.to_vec();
+        for _ in 0..levels {
+            sharp_edges = res.subdivide_once(&sharp_edges);
+        }
+        if levels == 0 {
+            return res;
+        }
+
+        if res.normals.len() == res.vertices.len() {
+            for normal in &mut res.normals {
+                if normal.mag_sq() > 0. {
+                    normal.normalize();
+                }
+            }
+        } else {
+            res.compute_smooth_normals_with_sharp_edges(DEFAULT_CREASE_ANGLE, &sharp_edges);
+        }
+        res
+    }
+
+    /// Returns the edges between triangles whose normals are more than `angle` (in radians)
+    /// apart, to be kept sharp by [`Object::subdivided`].
+    pub fn find_sharp_edges(&self, angle: f32) -> Vec<[usize; 2]> {
+        let canonical = self.canonical_vertices();
+        let mut edge_normals = HashMap::<[usize; 2], ([usize; 2], Vec<Vec3>)>::new();
+        for indices in &self.triangles {
+            let normal = self.get_triangle_unchecked(*indices).cross();
+            for corner in 0..3 {
+                let (a, b) = (indices[corner], indices[(corner + 1) % 3]);
+                let key = edge_key(canonical[a], canonical[b]);
+                edge_normals.entry(key).or_insert(([a, b], Vec::new())).1.push(normal);
+            }
+        }
+
+        let cos_angle = angle.cos();
+        let mut edges = edge_normals
+            .into_values()
+            .filter(|(_, normals)| {
+                normals.iter().any(|a| {
+                    normals.iter().any(|b| {
+                        a.mag_sq() > 0. && b.mag_sq() > 0. && a.normalized().dot(b.normalized()) < cos_angle
+                    })
+                })
+            })
+            .map(|(edge, _)| edge)
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    /// Applies 1 level of Loop subdivision, returning the sharp edges of the new triangles.
+    fn subdivide_once(&mut self, sharp_edges: &[[usize; 2]]) -> Vec<[usize; 2]> {
+        let vertex_count = self.vertices.len();
+        let canonical = self.canonical_vertices();
+        let group_edge = |a: usize, b: usize| edge_key(canonical[a], canonical[b]);
+        let sharp = sharp_edges
+            .iter()
+            .filter(|[a, b]| *a < vertex_count && *b < vertex_count)
+            .map(|[a, b]| group_edge(*a, *b))
+            .collect::<HashSet<_>>();
+
+        // * The vertices opposite of each edge, in the triangles on either side of it.
+        let mut opposite = HashMap::<[usize; 2], Vec<usize>>::new();
+        for indices in &self.triangles {
+            let groups = indices.map(|i| canonical[i]);
+            for corner in 0..3 {
+                let (a, b, c) = (groups[corner], groups[(corner + 1) % 3], groups[(corner + 2) % 3]);
+                opposite.entry(edge_key(a, b)).or_default().push(c);
+            }
+        }
+        let is_crease = |edge: &[usize; 2]| sharp.contains(edge) || opposite[edge].len() != 2;
+
+        let mut neighbours = HashMap::<usize, Vec<usize>>::new();
+        let mut creases = HashMap::<usize, Vec<usize>>::new();
+        for edge in opposite.keys() {
+            let [a, b] = *edge;
+            neighbours.entry(a).or_default().push(b);
+            neighbours.entry(b).or_default().push(a);
+            if is_crease(edge) {
+                creases.entry(a).or_default().push(b);
+                creases.entry(b).or_default().push(a);
+            }
+        }
+
+        // * Original vertices move towards their neighbours, or along the crease they're on.
+        let mut group_positions = HashMap::new();
+        for (group, neighbours) in &neighbours {
+            let v = self.vertices[*group];
+            let position = match creases.get(group).map_or(&[][..], Vec::as_slice) {
+                [] | [_] => {
+                    let n = neighbours.len() as f32;
+                    let beta = (5. / 8. - (3. / 8. + (2. * PI / n).cos() / 4.).powi(2)) / n;
+                    let sum = neighbours.iter().fold(Vec3::zero(), |sum, i| sum + self.vertices[*i]);
+                    v * (1. - n * beta) + sum * beta
+                }
+                [a, b] => v * 0.75 + (self.vertices[*a] + self.vertices[*b]) * 0.125,
+                _ => v,
+            };
+            group_positions.insert(*group, position);
+        }
+
+        let edge_position = |edge: &[usize; 2]| {
+            let [a, b] = *edge;
+            let midpoint = (self.vertices[a] + self.vertices[b]) * 0.5;
+            match opposite[edge].as_slice() {
+                [c, d] if !sharp.contains(edge) => {
+                    midpoint * 0.75 + (self.vertices[*c] + self.vertices[*d]) * 0.125
+                }
+                _ => midpoint,
+            }
+        };
+
+        let has_normals = self.normals.len() == vertex_count;
+        let has_uvs = self.uv_coords.len() == vertex_count;
+        let has_colors = self.colors.len() == vertex_count;
+
+        let mut vertices = (0..vertex_count)
+            .map(|i| group_positions.get(&canonical[i]).copied().unwrap_or(self.vertices[i]))
+            .collect::<Vec<_>>();
+        // * Normals are only normalized by `subdivided` once every level is done.
+        let mut normals = if has_normals { self.normals.clone() } else { Vec::new() };
+        let mut uv_coords = if has_uvs { self.uv_coords.clone() } else { Vec::new() };
+        let mut colors = if has_colors { self.colors.clone() } else { Vec::new() };
+
+        // * A new vertex is added on each edge, shared by the triangles on both sides of it
+        // * unless they use different vertices for it.
+        let mut edge_vertices = HashMap::<[usize; 2], usize>::new();
+        let mut edge_vertex = |a: usize, b: usize| {
+            *edge_vertices.entry(edge_key(a, b)).or_insert_with(|| {
+                vertices.push(edge_position(&group_edge(a, b)));
+                if has_normals {
+                    normals.push((self.normals[a] + self.normals[b]) * 0.5);
+                }
+                if has_uvs {
+                    uv_coords.push((self.uv_coords[a] + self.uv_coords[b]) * 0.5);
+                }
+                if has_colors {
+                    colors.push((self.colors[a] + self.colors[b]) * 0.5);
+                }
+                vertices.len() - 1
+            })
+        };
+
+        let mut triangles = Vec::with_capacity(self.triangles.len() * 4);
+        let mut new_sharp_edges = Vec::new();
+        for [a, b, c] in self.triangles.iter().copied() {
+            let (ab, bc, ca) = (edge_vertex(a, b), edge_vertex(b, c), edge_vertex(c, a));
+            triangles.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+            for (from, mid, to) in [(a, ab, b), (b, bc, c), (c, ca, a)] {
+                if sharp.contains(&group_edge(from, to)) {
+                    new_sharp_edges.extend([[from, mid], [mid, to]]);
+                }
+            }
+        }
+
+        self.vertices = vertices;
+        self.invalidate_bounds();
+        self.triangles = triangles;
+        self.normals = normals;
+        self.uv_coords = uv_coords;
+        self.colors = colors;
+        new_sharp_edges
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> [usize; 2] {
+    [a.min(b), a.max(b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::{tests::cube, Object};
+    use std::f32::consts::FRAC_PI_4;
+    use ultraviolet::Vec3;
+
+    /// Two flat panels meeting at a ridge along the z axis, folded by much less than the default
+    /// crease angle. The ridge is the edge from vertex 2 to vertex 3.
+    fn shallow_roof() -> Object {
+        let h = 0.1;
+        Object {
+            vertices: vec![
+                Vec3::new(-1., 0., 0.),
+                Vec3::new(-1., 0., 1.),
+                Vec3::new(0., h, 1.),
+                Vec3::new(0., h, 0.),
+                Vec3::new(1., 0., 1.),
+                Vec3::new(1., 0., 0.),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3], [3, 2, 4], [3, 4, 5]],
+            ..Object::new("roof")
+        }
+    }
+
+    #[test]
+    fn sharp_cube_keeps_its_bounds() {
+        let cube = cube();
+        let sharp_edges = cube.find_sharp_edges(FRAC_PI_4);
+        assert_eq!(sharp_edges.len(), 12);
+
+        let subdivided = cube.subdivided(2, &sharp_edges);
+        assert_eq!(subdivided.triangles.len(), 12 * 16);
+        let bounds = subdivided.local_bounds().unwrap();
+        assert_eq!((bounds.min, bounds.max), (Vec3::zero(), Vec3::one()));
+        // * Every vertex stays on one of the cube's faces.
+        for v in &subdivided.vertices {
+            assert!(v.as_array().iter().any(|c| *c == 0. || *c == 1.), "{:?} left the surface", v);
+        }
+        assert_eq!(subdivided.normals.len(), subdivided.vertices.len());
+    }
+
+    #[test]
+    fn smooth_cube_shrinks_and_gets_new_normals() {
+        let subdivided = cube().subdivided(2, &[]);
+        let bounds = subdivided.local_bounds().unwrap();
+        assert!(bounds.min.x > 0. && bounds.max.x < 1.);
+
+        // * The recomputed normals point away from the center of the rounded cube.
+        let center = Vec3::broadcast(0.5);
+        for (v, n) in subdivided.vertices.iter().zip(&subdivided.normals) {
+            assert!(n.dot((*v - center).normalized()) > 0.5);
+        }
+    }
+
+    #[test]
+    fn sharp_ridge_stays_flat_shaded() {
+        let roof = shallow_roof().subdivided(2, &[[2, 3]]);
+        for tri_index in 0..roof.triangles.len() {
+            let face_normal = roof.get_triangle_unchecked(roof.triangles[tri_index]).unit_normal();
+            for corner in roof.get_vertex_attributes(tri_index) {
+                assert!((corner.normal - face_normal).mag() < 1e-5, "{:?} is smoothed across the ridge", corner);
+            }
+        }
+
+        // * Without marking it, the ridge is shallow enough to be smoothed over.
+        let smooth = shallow_roof().subdivided(2, &[]);
+        let ridge_normal = smooth.normals.iter().zip(&smooth.vertices).find(|(_, v)| v.x == 0.).unwrap().0;
+        assert!((*ridge_normal - Vec3::unit_y()).mag() < 1e-5);
+    }
+
+    #[test]
+    fn keeps_authored_normals() {
+        let mut roof = shallow_roof();
+        roof.normals = roof.vertices.iter().map(|v| Vec3::new(v.x, 1., 0.).normalized()).collect();
+        let subdivided = roof.subdivided(2, &[[2, 3]]);
+
+        assert_eq!(subdivided.normals.len(), subdivided.vertices.len());
+        for (v, n) in subdivided.vertices.iter().zip(&subdivided.normals) {
+            // * Interpolated from the authored normals, which lean outwards along x.
+            assert!((n.mag() - 1.).abs() < 1e-5);
+            assert!((n.x - v.x / v.x.hypot(1.)).abs() < 0.1, "{:?} at {:?}", n, v);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::path::Path;
+
+use crate::error::{AssetError, AssetFormat};
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    DynamicImage, ImageBuffer, ImageError, RgbaImage,
+};
+use ultraviolet::{Vec2, Vec4};
+
+/// How texture coordinates outside of the `0..=1` range are mapped back onto the texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// Tiles the texture.
+    #[default]
+    Repeat,
+    /// Stretches the texels along the texture's edges.
+    Clamp,
+    /// Tiles the texture, flipping every other tile.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Maps a texel coordinate onto the `0..size` range.
+    fn apply(&self, coord: i64, size: u32) -> u32 {
+        let size = size as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => coord.rem_euclid(size),
+            WrapMode::Clamp => coord.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let t = coord.rem_euclid(2 * size);
+                if t >= size {
+                    2 * size - 1 - t
+                } else {
+                    t
+                }
+            }
+        };
+        wrapped as u32
+    }
+}
+
+/// How a texture is sampled between texel centers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterMode {
+    /// Uses the closest texel.
+    Nearest,
+    /// Blends between the 4 closest texels.
+    #[default]
+    Bilinear,
+}
+
+#[derive(Clone)]
+pub struct Texture {
+    pub image: RgbaImage,
+    /// How `u` coordinates wrap, across the image's width.
+    pub wrap_mode_u: WrapMode,
+    /// How `v` coordinates wrap, across the image's height.
+    pub wrap_mode_v: WrapMode,
+    pub filter_mode: FilterMode,
+}
+
+impl Texture {
+    pub fn new(image: DynamicImage) -> Self {
+        Self {
+            image: image.into_rgba8(),
+            wrap_mode_u: WrapMode::default(),
+            wrap_mode_v: WrapMode::default(),
+            filter_mode: FilterMode::default(),
+        }
+    }
+
+    /// Loads an image file, in any of the formats supported by the `image` crate.
+    pub fn load(path: &Path) -> Result<Self, AssetError> {
+        let reader = image::ImageReader::open(path).map_err(|source| AssetError::MissingTexture {
+            path: path.to_owned(),
+            source,
+        })?;
+        let image = reader.decode().map_err(|source| AssetError::UndecodableTexture {
+            path: path.to_owned(),
+            source,
+        })?;
+        Ok(Self::new(image))
+    }
+
+    /// Loads one of the images of the glTF file at `path`. Image files next to it are loaded with
+    /// [`Texture::load`], so that their errors name the image instead of the glTF file.
+    pub(crate) fn load_gltf_image(
+        path: &Path,
+        image: &gltf::Image,
+        buffers: &[gltf::buffer::Data],
+    ) -> Result<RgbaImage, AssetError> {
+        let base = path.parent().unwrap_or(Path::new(""));
+        match image.source() {
+            // * URIs without a scheme are relative paths, which can be percent-encoded.
+            gltf::image::Source::Uri { uri, .. } if !uri.contains(':') => {
+                let relative = urlencoding::decode(uri).map_or_else(|_| uri.into(), |uri| uri.into_owned());
+                Ok(Self::load(&base.join(relative))?.image)
+            }
+            source => {
+                let data = gltf::image::Data::from_source(source, Some(base), buffers).map_err(|e| match e {
+                    gltf::Error::Image(source) => AssetError::UndecodableTexture {
+                        path: path.to_owned(),
+                        source,
+                    },
+                    _ => AssetError::Invalid {
+                        path: path.to_owned(),
+                        format: AssetFormat::Gltf,
+                        source: Box::new(e),
+                    },
+                })?;
+                Self::image_from_gltf(path, &data)
+            }
+        }
+    }
+
+    /// Converts an image embedded in the glTF file at `path`, whatever its bit depth. 16-bit and
+    /// floating point channels are scaled down to 8 bits.
+    pub(crate) fn image_from_gltf(path: &Path, data: &gltf::image::Data) -> Result<RgbaImage, AssetError> {
+        use gltf::image::Format;
+
+        // * `gltf` stores wider channels as their bytes in native endianness.
+        let (width, height) = (data.width, data.height);
+        let u8s = || data.pixels.clone();
+        let u16s = || {
+            data.pixels
+                .chunks_exact(2)
+                .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+                .collect::<Vec<_>>()
+        };
+        let f32s = || {
+            data.pixels
+                .chunks_exact(4)
+                .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect::<Vec<_>>()
+        };
+
+        let image = match data.format {
+            Format::R8 => ImageBuffer::from_raw(width, height, u8s()).map(DynamicImage::ImageLuma8),
+            Format::R8G8 => {
+                ImageBuffer::from_raw(width, height, u8s()).map(DynamicImage::ImageLumaA8)
+            }
+            Format::R8G8B8 => {
+                ImageBuffer::from_raw(width, height, u8s()).map(DynamicImage::ImageRgb8)
+            }
+            Format::R8G8B8A8 => {
+                ImageBuffer::from_raw(width, height, u8s()).map(DynamicImage::ImageRgba8)
+            }
+            Format::R16 => {
+                ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageLuma16)
+            }
+            Format::R16G16 => {
+                ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageLumaA16)
+            }
+            Format::R16G16B16 => {
+                ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageRgb16)
+            }
+            Format::R16G16B16A16 => {
+                ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageRgba16)
+            }
+            Format::R32G32B32FLOAT => {
+                ImageBuffer::from_raw(width, height, f32s()).map(DynamicImage::ImageRgb32F)
+            }
+            Format::R32G32B32A32FLOAT => {
+                ImageBuffer::from_raw(width, height, f32s()).map(DynamicImage::ImageRgba32F)
+            }
+        };
+
+        match image {
+            Some(image) => Ok(image.into_rgba8()),
+            None => Err(AssetError::UndecodableTexture {
+                path: path.to_owned(),
+                source: ImageError::Parameter(ParameterError::from_kind(
+                    ParameterErrorKind::DimensionMismatch,
+                )),
+            }),
+        }
+    }
+
+    /// Converts a glTF texture, along with its sampler's wrap and filter modes. `images` are the
+    /// glTF file's images loaded by [`Texture::load_gltf_image`]. Returns `None` if the
+    /// texture's image isn't one of them.
+    pub(crate) fn from_gltf(texture: &gltf::Texture, images: &[RgbaImage]) -> Option<Self> {
+        use gltf::texture::{MagFilter, WrappingMode};
+
+        let wrap_mode = |mode| match mode {
+            WrappingMode::ClampToEdge => WrapMode::Clamp,
+            WrappingMode::MirroredRepeat => WrapMode::Mirror,
+            WrappingMode::Repeat => WrapMode::Repeat,
+        };
+
+        let sampler = texture.sampler();
+        Some(Self {
+            image: images.get(texture.source().index())?.clone(),
+            wrap_mode_u: wrap_mode(sampler.wrap_s()),
+            wrap_mode_v: wrap_mode(sampler.wrap_t()),
+            filter_mode: match sampler.mag_filter() {
+                Some(MagFilter::Nearest) => FilterMode::Nearest,
+                _ => FilterMode::Bilinear,
+            },
+        })
+    }
+
+    /// Samples the texture at `uv`, returning an RGBA colour with channels in the range `0..=1`.
+    /// Like OBJ texture coordinates, `v` points up from the bottom of the image. Empty images
+    /// have no texels to wrap onto, so they sample as transparent black.
+    pub fn sample(&self, uv: Vec2) -> Vec4 {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Vec4::zero();
+        }
+
+        let x = uv.x * self.image.width() as f32;
+        let y = (1. - uv.y) * self.image.height() as f32;
+
+        match self.filter_mode {
+            FilterMode::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            FilterMode::Bilinear => {
+                // * Texel centers sit at half-integer coordinates.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1. - tx) + self.texel(x0 + 1, y0) * tx;
+                let bottom = self.texel(x0, y0 + 1) * (1. - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+                top * (1. - ty) + bottom * ty
+            }
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec4 {
+        let x = self.wrap_mode_u.apply(x, self.image.width());
+        let y = self.wrap_mode_v.apply(y, self.image.height());
+        let [r, g, b, a] = self.image.get_pixel(x, y).0;
+        Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gltf::image::{Data, Format};
+
+    #[test]
+    fn converts_wide_gltf_images() {
+        let path = Path::new("test.gltf");
+        let luma16 = Data {
+            pixels: [0u16, u16::MAX].iter().flat_map(|c| c.to_ne_bytes()).collect(),
+            format: Format::R16,
+            width: 2,
+            height: 1,
+        };
+        let image = Texture::image_from_gltf(path, &luma16).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 255, 255, 255]);
+
+        let rgba32 = Data {
+            pixels: [1f32, 0., 0.5, 1.].iter().flat_map(|c| c.to_ne_bytes()).collect(),
+            format: Format::R32G32B32A32FLOAT,
+            width: 1,
+            height: 1,
+        };
+        let image = Texture::image_from_gltf(path, &rgba32).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 128, 255]);
+
+        let truncated = Data { pixels: vec![0; 3], ..luma16 };
+        assert!(matches!(
+            Texture::image_from_gltf(path, &truncated),
+            Err(AssetError::UndecodableTexture { .. })
+        ));
+    }
+
+    #[test]
+    fn wraps_each_axis_separately() {
+        let mut image = RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        let texture = Texture {
+            image,
+            wrap_mode_u: WrapMode::Repeat,
+            wrap_mode_v: WrapMode::Clamp,
+            filter_mode: FilterMode::Nearest,
+        };
+
+        // * The red texel is at the top left, where `v` is just below 1.
+        assert_eq!(texture.sample(Vec2::new(1.25, 0.75)).x, 1.);
+        assert_eq!(texture.sample(Vec2::new(0.25, 1.75)).x, 1.);
+        assert_eq!(texture.sample(Vec2::new(0.25, -0.25)).x, 0.);
+        assert_eq!(texture.sample(Vec2::new(-0.75, 1.25)).x, 1.);
+    }
+}
@@ -1,15 +1,19 @@
-use ultraviolet::{Isometry3, Mat4, Vec2, Vec3};
+use ultraviolet::{Isometry3, Mat4, Vec3};
 
-#[derive(Debug, Clone, Copy)]
-pub struct Triangle2D {
-    pub v0: Vec2,
-    pub v1: Vec2,
-    pub v2: Vec2,
+/// The order in which a triangle's vertices appear when looking at its front face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Winding {
+    Clockwise,
+    #[default]
+    CounterClockwise,
 }
 
-impl Triangle2D {
-    pub fn new(v0: Vec2, v1: Vec2, v2: Vec2) -> Self {
-        Self { v0, v1, v2 }
+impl Winding {
+    pub fn reversed(self) -> Self {
+        match self {
+            Winding::Clockwise => Winding::CounterClockwise,
+            Winding::CounterClockwise => Winding::Clockwise,
+        }
     }
 }
 
@@ -25,6 +29,22 @@ impl Triangle3D {
         Self { v0, v1, v2 }
     }
 
+    /// Returns the triangle's normal as given by the right-hand rule, scaled by twice its area.
+    pub fn cross(&self) -> Vec3 {
+        (self.v1 - self.v0).cross(self.v2 - self.v0)
+    }
+
+    /// Returns the triangle's normal as given by the right-hand rule with a length of 1, or zero
+    /// if the triangle is degenerate and so has no direction to face.
+    pub fn unit_normal(&self) -> Vec3 {
+        let cross = self.cross();
+        if cross.mag_sq() > 0. {
+            cross.normalized()
+        } else {
+            cross
+        }
+    }
+
     pub fn apply_transform(&self, transform: Isometry3) -> Triangle3D {
         Triangle3D::new(
             transform.transform_vec(self.v0),
@@ -40,18 +60,6 @@ impl Triangle3D {
             matrix.transform_point3(self.v2),
         )
     }
-
-    pub fn truncated(&self) -> Triangle2D {
-        Triangle2D::new(
-            self.v0.truncated(),
-            self.v1.truncated(),
-            self.v2.truncated(),
-        )
-    }
-
-    pub fn truncated_include_z(&self) -> (Triangle2D, [f32; 3]) {
-        (self.truncated(), [self.v0.z, self.v1.z, self.v2.z])
-    }
 }
 
 impl From<stl_io::Triangle> for Triangle3D {
@@ -60,18 +68,36 @@ impl From<stl_io::Triangle> for Triangle3D {
             v0: Vec3::new(
                 value.vertices[0][0],
                 value.vertices[0][1],
-                value.vertices[0][3],
+                value.vertices[0][2],
             ),
             v1: Vec3::new(
                 value.vertices[1][0],
                 value.vertices[1][1],
-                value.vertices[1][3],
+                value.vertices[1][2],
             ),
             v2: Vec3::new(
                 value.vertices[2][0],
                 value.vertices[2][1],
-                value.vertices[2][3],
+                value.vertices[2][2],
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Triangle3D;
+    use stl_io::Vector;
+    use ultraviolet::Vec3;
+
+    #[test]
+    fn converts_stl_triangle() {
+        let tri = Triangle3D::from(stl_io::Triangle {
+            normal: Vector::new([0., 0., 1.]),
+            vertices: [Vector::new([1., 2., 3.]), Vector::new([4., 5., 6.]), Vector::new([7., 8., 9.])],
+        });
+        assert_eq!(tri.v0, Vec3::new(1., 2., 3.));
+        assert_eq!(tri.v1, Vec3::new(4., 5., 6.));
+        assert_eq!(tri.v2, Vec3::new(7., 8., 9.));
+    }
+}
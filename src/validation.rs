@@ -0,0 +1,373 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{mesh::DEFAULT_CREASE_ANGLE, object::Object};
+use ultraviolet::Vec3;
+
+/// Problems found in an [`Object`]'s geometry by [`Object::validate`]. Triangles are given by
+/// their index in [`Object::triangles`], and edges by the indices of their 2 vertices.
+///
+/// Vertices at exactly the same position are treated as one when looking at edges, so that
+/// vertices split along UV seams don't show up as holes.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    /// Triangles using vertex indices that are out of range.
+    pub out_of_range_triangles: Vec<usize>,
+    /// Triangles with no area, because 2 of their vertices are the same or they're collinear.
+    pub degenerate_triangles: Vec<usize>,
+    /// Edges shared by more than 2 triangles.
+    pub non_manifold_edges: Vec<[usize; 2]>,
+    /// Edges shared by 2 triangles that go along it in the same direction,
+    /// meaning that one of them is facing the wrong way.
+    pub inconsistent_edges: Vec<[usize; 2]>,
+    /// Loops of edges that only belong to 1 triangle, in the order they go around the hole.
+    pub holes: Vec<Vec<usize>>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.out_of_range_triangles.is_empty()
+            && self.degenerate_triangles.is_empty()
+            && self.non_manifold_edges.is_empty()
+            && self.inconsistent_edges.is_empty()
+            && self.holes.is_empty()
+    }
+}
+
+/// Which problems are fixed by [`Object::repair`].
+#[derive(Debug, Clone, Copy)]
+pub struct RepairSettings {
+    /// Removes degenerate triangles. Triangles with out of range indices are always removed.
+    pub remove_degenerates: bool,
+    /// Flips triangles to face the same way as their neighbours, and turns closed meshes
+    /// inside out if they face inwards.
+    pub unify_winding: bool,
+    /// Fills holes with at most this many edges with a fan of triangles, where 0 disables filling.
+    pub max_hole_edges: usize,
+}
+
+impl Default for RepairSettings {
+    fn default() -> Self {
+        Self {
+            remove_degenerates: true,
+            unify_winding: true,
+            max_hole_edges: 8,
+        }
+    }
+}
+
+/// The triangles using an edge, and whether each goes along it from its lower to its higher vertex.
+type EdgeMap = HashMap<[usize; 2], Vec<(usize, bool)>>;
+
+impl Object {
+    /// Checks the object's triangles for problems, without changing anything.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let canonical = self.canonical_vertices();
+
+        for (tri_index, indices) in self.triangles.iter().enumerate() {
+            if indices.iter().any(|i| *i >= self.vertices.len()) {
+                report.out_of_range_triangles.push(tri_index);
+            } else if self.is_degenerate(&canonical, *indices) {
+                report.degenerate_triangles.push(tri_index);
+            }
+        }
+
+        let edges = self.edge_map(&canonical);
+        for (edge, users) in &edges {
+            match users.as_slice() {
+                [_, _, _, ..] => report.non_manifold_edges.push(*edge),
+                [(_, a), (_, b)] if a == b => report.inconsistent_edges.push(*edge),
+                _ => {}
+            }
+        }
+        report.non_manifold_edges.sort_unstable();
+        report.inconsistent_edges.sort_unstable();
+        report.holes = self.find_holes(&edges);
+
+        report
+    }
+
+    /// Fixes the problems selected by `settings`, returning a report of the problems left.
+    pub fn repair(&mut self, settings: &RepairSettings) -> ValidationReport {
+        let report = self.validate();
+        let mut removed = vec![false; self.triangles.len()];
+        for tri_index in &report.out_of_range_triangles {
+            removed[*tri_index] = true;
+        }
+        if settings.remove_degenerates {
+            for tri_index in &report.degenerate_triangles {
+                removed[*tri_index] = true;
+            }
+        }
+        self.retain_triangles(|tri_index| !removed[tri_index]);
+
+        if settings.unify_winding {
+            self.unify_winding();
+        }
+
+        let mut filled = false;
+        if settings.max_hole_edges > 0 {
+            let canonical = self.canonical_vertices();
+            let holes = self.find_holes(&self.edge_map(&canonical));
+            for hole in holes.iter().filter(|hole| hole.len() <= settings.max_hole_edges) {
+                // * Hole loops go the same way as the triangles around them, so the fan has to
+                // * go the other way to match their winding.
+                for i in 1..hole.len() - 1 {
+                    self.triangles.push([hole[0], hole[i + 1], hole[i]]);
+                }
+                filled = true;
+            }
+        }
+
+        // * Filling holes can close a mesh, which lets it be turned the right way out.
+        if settings.unify_winding && filled {
+            self.unify_winding();
+        }
+
+        self.validate()
+    }
+
+    /// Removes triangles with out of range indices, so that the object can be rendered.
+    /// Used by the loaders, as the files they read can't be trusted.
+    pub fn remove_out_of_range_triangles(&mut self) {
+        let vertex_count = self.vertices.len();
+        let valid = self
+            .triangles
+            .iter()
+            .map(|indices| indices.iter().all(|i| *i < vertex_count))
+            .collect::<Vec<_>>();
+        self.retain_triangles(|tri_index| valid[tri_index]);
+    }
+
+    /// Keeps only the triangles for which `keep` returns `true`.
+    fn retain_triangles<F: Fn(usize) -> bool>(&mut self, keep: F) {
+        let mut tri_index = 0;
+        self.triangles.retain(|_| {
+            tri_index += 1;
+            keep(tri_index - 1)
+        });
+    }
+
+    /// Returns, for each vertex, the index of the first vertex at exactly the same position.
+    pub fn canonical_vertices(&self) -> Vec<usize> {
+        let mut first_at = HashMap::new();
+        self.vertices
+            .iter()
+            .enumerate()
+            .map(|(i, v)| *first_at.entry([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]).or_insert(i))
+            .collect()
+    }
+
+    fn is_degenerate(&self, canonical: &[usize], indices: [usize; 3]) -> bool {
+        let [a, b, c] = indices.map(|i| canonical[i]);
+        if a == b || b == c || c == a {
+            return true;
+        }
+        let [a, b, c] = indices.map(|i| self.vertices[i]);
+        let longest = (b - a).mag_sq().max((c - b).mag_sq()).max((a - c).mag_sq());
+        (b - a).cross(c - a).mag() <= f32::EPSILON * longest
+    }
+
+    /// Maps each edge of the valid triangles to the triangles using it, see [`EdgeMap`].
+    fn edge_map(&self, canonical: &[usize]) -> EdgeMap {
+        let mut edges = EdgeMap::new();
+        for (tri_index, indices) in self.triangles.iter().enumerate() {
+            if indices.iter().any(|i| *i >= self.vertices.len()) || self.is_degenerate(canonical, *indices) {
+                continue;
+            }
+            let [a, b, c] = indices.map(|i| canonical[i]);
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                edges
+                    .entry([from.min(to), from.max(to)])
+                    .or_default()
+                    .push((tri_index, from < to));
+            }
+        }
+        edges
+    }
+
+    /// Follows the edges that only belong to 1 triangle around each hole. Boundaries that
+    /// don't form a simple loop, such as 2 holes touching at a vertex, are skipped.
+    fn find_holes(&self, edges: &EdgeMap) -> Vec<Vec<usize>> {
+        let mut next = HashMap::<usize, Vec<usize>>::new();
+        for (edge, users) in edges {
+            if let [(_, forward)] = users.as_slice() {
+                let (from, to) = if *forward { (edge[0], edge[1]) } else { (edge[1], edge[0]) };
+                next.entry(from).or_default().push(to);
+            }
+        }
+
+        let mut starts = next.keys().copied().collect::<Vec<_>>();
+        starts.sort_unstable();
+
+        let mut holes = Vec::new();
+        for start in starts {
+            let mut hole = vec![start];
+            let mut current = start;
+            let closed = loop {
+                match next.get(&current).map(Vec::as_slice) {
+                    Some([to]) if *to == start => break true,
+                    Some([to]) if !hole.contains(to) => {
+                        hole.push(*to);
+                        current = *to;
+                    }
+                    _ => break false,
+                }
+            };
+            if closed && hole.len() >= 3 {
+                for v in &hole {
+                    next.remove(v);
+                }
+                holes.push(hole);
+            }
+        }
+        holes
+    }
+
+    /// Flips triangles so that neighbours go along their shared edges in opposite directions.
+    fn unify_winding(&mut self) {
+        let canonical = self.canonical_vertices();
+        let edges = self.edge_map(&canonical);
+        let mut neighbours = vec![Vec::new(); self.triangles.len()];
+        for users in edges.values() {
+            if let [(a, a_forward), (b, b_forward)] = users.as_slice() {
+                // * Whether `b` has to be flipped relative to `a`.
+                let flip = a_forward == b_forward;
+                neighbours[*a].push((*b, flip));
+                neighbours[*b].push((*a, flip));
+            }
+        }
+
+        let mut flipped = vec![None; self.triangles.len()];
+        for start in 0..self.triangles.len() {
+            if flipped[start].is_some() {
+                continue;
+            }
+
+            flipped[start] = Some(false);
+            let mut component = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(tri_index) = queue.pop_front() {
+                let tri_flipped = flipped[tri_index].unwrap();
+                for (neighbour, flip) in &neighbours[tri_index] {
+                    if flipped[*neighbour].is_none() {
+                        flipped[*neighbour] = Some(tri_flipped != *flip);
+                        component.push(*neighbour);
+                        queue.push_back(*neighbour);
+                    }
+                }
+            }
+
+            // * A closed mesh facing inwards has a negative volume, so the whole component gets
+            // * flipped. The volume of an open mesh doesn't mean anything, so it's left as is.
+            let closed = component
+                .iter()
+                .all(|tri_index| neighbours[*tri_index].len() == 3);
+            let volume = component
+                .iter()
+                .map(|tri_index| {
+                    let tri = self.get_triangle_unchecked(self.triangles[*tri_index]);
+                    let sign = if flipped[*tri_index] == Some(true) { -1. } else { 1. };
+                    sign * signed_volume(tri.v0, tri.v1, tri.v2)
+                })
+                .sum::<f32>();
+            if closed && volume < 0. {
+                for tri_index in &component {
+                    flipped[*tri_index] = flipped[*tri_index].map(|f| !f);
+                }
+            }
+        }
+
+        let flipped = flipped.into_iter().map(|f| f == Some(true)).collect::<Vec<_>>();
+        for (indices, flipped) in self.triangles.iter_mut().zip(&flipped) {
+            if *flipped {
+                indices.swap(1, 2);
+            }
+        }
+        self.flip_normals(&flipped);
+    }
+
+    /// Turns the normals of the triangles flipped by [`Object::unify_winding`] around to match.
+    /// Vertices shared by flipped and unflipped triangles can't face both ways, so the normals
+    /// are recomputed instead if there are any.
+    fn flip_normals(&mut self, flipped: &[bool]) {
+        if self.normals.len() == self.vertices.len() {
+            let mut vertex_flipped = vec![None; self.vertices.len()];
+            let mut mixed = false;
+            for (indices, flipped) in self.triangles.iter().zip(flipped) {
+                for i in indices {
+                    mixed |= *vertex_flipped[*i].get_or_insert(*flipped) != *flipped;
+                }
+            }
+
+            if mixed {
+                self.compute_smooth_normals(DEFAULT_CREASE_ANGLE);
+            } else {
+                for (normal, flipped) in self.normals.iter_mut().zip(vertex_flipped) {
+                    if flipped == Some(true) {
+                        *normal = -*normal;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the signed volume of the tetrahedron between the origin and a triangle.
+fn signed_volume(a: Vec3, b: Vec3, c: Vec3) -> f32 {
+    a.dot(b.cross(c)) / 6.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::tests::cube;
+
+    /// Whether every triangle of a mesh centered on (0.5, 0.5, 0.5) faces outwards.
+    fn faces_outwards(obj: &Object) -> bool {
+        obj.triangles.iter().all(|indices| {
+            let tri = obj.get_triangle_unchecked(*indices);
+            let center = (tri.v0 + tri.v1 + tri.v2) / 3.;
+            tri.cross().dot(center - Vec3::broadcast(0.5)) > 0.
+        })
+    }
+
+    /// A cube with its last triangle missing and its first one facing inwards.
+    fn broken_cube() -> Object {
+        let mut obj = cube();
+        obj.triangles.pop();
+        obj.triangles[0].swap(1, 2);
+        obj
+    }
+
+    #[test]
+    fn finds_hole_and_flipped_face() {
+        let report = broken_cube().validate();
+        assert!(!report.is_valid());
+        assert_eq!(report.holes.len(), 1);
+        assert_eq!(report.holes[0].len(), 3);
+        // * The flipped triangle disagrees with all 3 of its neighbours.
+        assert_eq!(report.inconsistent_edges.len(), 3);
+        assert!(report.out_of_range_triangles.is_empty() && report.degenerate_triangles.is_empty());
+    }
+
+    #[test]
+    fn repairs_hole_and_flipped_face() {
+        let mut obj = broken_cube();
+        let report = obj.repair(&RepairSettings::default());
+        assert!(report.is_valid(), "{:?}", report);
+        assert_eq!(obj.triangles.len(), 12);
+        assert!(faces_outwards(&obj));
+    }
+
+    #[test]
+    fn repair_flips_normals_with_triangles() {
+        // * A cube turned inside out, with vertex normals pointing inwards.
+        let mut obj = cube();
+        obj.triangles.iter_mut().for_each(|indices| indices.swap(1, 2));
+        obj.normals = obj.vertices.iter().map(|v| (Vec3::broadcast(0.5) - *v).normalized()).collect();
+        obj.repair(&RepairSettings::default());
+        assert!(faces_outwards(&obj));
+        assert!(obj.vertices.iter().zip(&obj.normals).all(|(v, n)| n.dot(*v - Vec3::broadcast(0.5)) > 0.));
+    }
+}
@@ -0,0 +1,56 @@
+use std::ops::{Add, Mul};
+
+use ultraviolet::{Vec2, Vec3};
+
+/// Per-vertex attributes that get interpolated across the surface of a triangle.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexAttributes {
+    /// World space position, used for lighting.
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub color: Vec3,
+}
+
+impl Default for VertexAttributes {
+    fn default() -> Self {
+        Self {
+            position: Vec3::zero(),
+            normal: Vec3::zero(),
+            uv: Vec2::zero(),
+            color: Vec3::one(),
+        }
+    }
+}
+
+impl VertexAttributes {
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        *self * (1. - t) + *other * t
+    }
+}
+
+impl Add for VertexAttributes {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            position: self.position + rhs.position,
+            normal: self.normal + rhs.normal,
+            uv: self.uv + rhs.uv,
+            color: self.color + rhs.color,
+        }
+    }
+}
+
+impl Mul<f32> for VertexAttributes {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self {
+            position: self.position * rhs,
+            normal: self.normal * rhs,
+            uv: self.uv * rhs,
+            color: self.color * rhs,
+        }
+    }
+}